@@ -1,14 +1,16 @@
 use proc_macro::{TokenStream};
-use syn::{parse_macro_input, DeriveInput};
+use syn::{parse_macro_input, DeriveInput, Meta, NestedMeta};
 use quote::quote;
 
-#[proc_macro_derive(Event)]
+#[proc_macro_derive(Event, attributes(event))]
 pub fn event_derive(input: TokenStream) -> TokenStream {
   let input = parse_macro_input!(input as DeriveInput);
   let name = &input.ident;
   let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
-  let expanded = quote! {
+  let recordable = has_event_flag(&input, "record");
+
+  let mut expanded = quote! {
     impl #impl_generics isle_traits::event::Event for #name #ty_generics #where_clause {
       fn as_any(&self) -> &dyn std::any::Any {
         self
@@ -16,9 +18,42 @@ pub fn event_derive(input: TokenStream) -> TokenStream {
     }
   };
 
+  if recordable {
+    expanded = quote! {
+      #expanded
+
+      impl #impl_generics isle_traits::event::RecordableEvent for #name #ty_generics #where_clause {
+        fn event_name() -> &'static str {
+          concat!(module_path!(), "::", stringify!(#name))
+        }
+        fn serialize_event(&self) -> Option<String> {
+          isle_traits::serde_json::to_string(self).ok()
+        }
+        fn deserialize_event(payload: &str) -> Option<Self> {
+          isle_traits::serde_json::from_str(payload).ok()
+        }
+      }
+    };
+  }
+
   TokenStream::from(expanded)
 }
 
+fn has_event_flag(input: &DeriveInput, flag: &str) -> bool {
+  input
+    .attrs
+    .iter()
+    .filter(|attr| attr.path.segments.len() == 1 && attr.path.segments[0].ident == "event")
+    .filter_map(|attr| attr.parse_meta().ok())
+    .any(|meta| match meta {
+      Meta::List(list) => list.nested.iter().any(|nested| match nested {
+        NestedMeta::Meta(Meta::Word(word)) => word == flag,
+        _ => false,
+      }),
+      _ => false,
+    })
+}
+
 #[proc_macro_derive(Component)]
 pub fn component_derive(input: TokenStream) -> TokenStream {
   let input = parse_macro_input!(input as DeriveInput);
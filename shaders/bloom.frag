@@ -0,0 +1,33 @@
+#version 450
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+// A zero direction keeps the parts of the source above the threshold, any
+// other direction blurs along it.
+layout(push_constant) uniform Bloom {
+  vec2 direction;
+  float threshold;
+} bloom;
+
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+  if (bloom.direction == vec2(0.0)) {
+    vec3 color = texture(source, uv).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    float excess = max(brightness - bloom.threshold, 0.0);
+    f_color = vec4(color * excess / max(brightness, 0.0001), 1.0);
+    return;
+  }
+
+  vec2 step = bloom.direction / vec2(textureSize(source, 0));
+  vec3 color = texture(source, uv).rgb * weights[0];
+  for (int i = 1; i < 5; i++) {
+    color += texture(source, uv + step * i).rgb * weights[i];
+    color += texture(source, uv - step * i).rgb * weights[i];
+  }
+  f_color = vec4(color, 1.0);
+}
@@ -0,0 +1,132 @@
+#version 450
+layout(location = 0) in vec4 in_color;
+layout(location = 1) in vec2 in_uv;
+layout(location = 2) in vec3 in_normal;
+layout(location = 3) in vec3 in_position;
+layout(location = 4) in vec3 in_to_camera;
+
+layout(location = 0) out vec4 f_color;
+
+const int MAX_LIGHTS = 16;
+const int MAX_SHADOWS = 4;
+const float DIRECTIONAL = 0.0;
+const float SPOT = 2.0;
+const float SHININESS = 32.0;
+
+struct Light {
+  // w is the kind: 0 for directional, 1 for point and 2 for spot lights.
+  vec4 position;
+  // w is the range.
+  vec4 direction;
+  // Already scaled by the intensity.
+  vec4 color;
+  // Cosines of the inner and outer spot angles.
+  vec4 cone;
+  // Takes world space to the shadow map's clip space.
+  mat4 shadow_matrix;
+  // x is the shadow map, or -1 for none, y the normal bias and z the PCF radius.
+  vec4 shadow;
+};
+
+layout(set = 0, binding = 1) uniform Lights {
+  Light lights[MAX_LIGHTS];
+  vec4 ambient;
+  int count;
+} lights;
+
+layout(set = 0, binding = 2) uniform sampler2DShadow shadow_maps[MAX_SHADOWS];
+
+// Untextured actors sample a 1x1 white texture.
+layout(set = 1, binding = 0) uniform sampler2D tex;
+
+// Shadow maps are only indexed with constants, since indexing them with a value
+// from the light buffer needs a device feature. Keep one case per map.
+vec2 get_shadow_texel(int map) {
+  switch (map) {
+    case 0: return 1.0 / vec2(textureSize(shadow_maps[0], 0));
+    case 1: return 1.0 / vec2(textureSize(shadow_maps[1], 0));
+    case 2: return 1.0 / vec2(textureSize(shadow_maps[2], 0));
+    default: return 1.0 / vec2(textureSize(shadow_maps[3], 0));
+  }
+}
+
+float sample_shadow(int map, vec3 coords) {
+  switch (map) {
+    case 0: return texture(shadow_maps[0], coords);
+    case 1: return texture(shadow_maps[1], coords);
+    case 2: return texture(shadow_maps[2], coords);
+    default: return texture(shadow_maps[3], coords);
+  }
+}
+
+// Returns how much of the light reaches the surface, averaging depth
+// comparisons over a (2r + 1)^2 texel square.
+float get_shadow(Light light, vec3 position, vec3 normal) {
+  int map = int(light.shadow.x);
+  if (map < 0) {
+    return 1.0;
+  }
+
+  // Offsetting along the normal hides acne on surfaces facing away from the
+  // light at grazing angles.
+  vec4 clip = light.shadow_matrix * vec4(position + normal * light.shadow.y, 1.0);
+  vec3 coords = clip.xyz / clip.w;
+  if (coords.z >= 1.0) {
+    return 1.0;
+  }
+
+  vec2 uv = coords.xy * 0.5 + 0.5;
+  vec2 texel = get_shadow_texel(map);
+  int radius = int(light.shadow.z);
+
+  float lit = 0.0;
+  for (int x = -radius; x <= radius; x++) {
+    for (int y = -radius; y <= radius; y++) {
+      lit += sample_shadow(map, vec3(uv + vec2(x, y) * texel, coords.z));
+    }
+  }
+
+  float size = float(2 * radius + 1);
+  return lit / (size * size);
+}
+
+void main() {
+  vec4 albedo = in_color * texture(tex, in_uv);
+  vec3 to_camera = normalize(in_to_camera);
+  // Meshes without normals are lit as if they faced the camera.
+  vec3 normal = length(in_normal) > 0.0 ? normalize(in_normal) : to_camera;
+
+  vec3 diffuse = lights.ambient.rgb;
+  vec3 specular = vec3(0.0);
+  for (int i = 0; i < min(lights.count, MAX_LIGHTS); i++) {
+    Light light = lights.lights[i];
+
+    vec3 to_light = -light.direction.xyz;
+    float attenuation = 1.0;
+    if (light.position.w != DIRECTIONAL) {
+      vec3 offset = light.position.xyz - in_position;
+      float distance = length(offset);
+      to_light = offset / max(distance, 0.0001);
+
+      // Inverse square falloff, windowed so it reaches zero at the range.
+      float window = clamp(1.0 - pow(distance / light.direction.w, 4.0), 0.0, 1.0);
+      attenuation = window * window / (distance * distance + 1.0);
+    }
+    if (light.position.w == SPOT) {
+      float angle = dot(-to_light, light.direction.xyz);
+      attenuation *= smoothstep(light.cone.y, light.cone.x, angle);
+    }
+    attenuation *= get_shadow(light, in_position, normal);
+
+    // Blinn-Phong: the highlight peaks where the normal bisects the light and
+    // view directions.
+    float lambert = max(dot(normal, to_light), 0.0);
+    vec3 halfway = normalize(to_light + to_camera);
+    float highlight = lambert > 0.0 ? pow(max(dot(normal, halfway), 0.0), SHININESS) : 0.0;
+
+    diffuse += light.color.rgb * lambert * attenuation;
+    specular += light.color.rgb * highlight * attenuation;
+  }
+
+  f_color = vec4(albedo.rgb * diffuse + specular, albedo.a);
+}
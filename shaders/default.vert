@@ -0,0 +1,40 @@
+#version 450
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 color;
+layout(location = 2) in vec2 uv;
+layout(location = 3) in vec3 normal;
+layout(location = 4) in vec4 instance_model_0;
+layout(location = 5) in vec4 instance_model_1;
+layout(location = 6) in vec4 instance_model_2;
+layout(location = 7) in vec4 instance_model_3;
+layout(location = 8) in vec4 instance_color;
+
+layout(location = 0) out vec4 out_color;
+layout(location = 1) out vec2 out_uv;
+layout(location = 2) out vec3 out_normal;
+layout(location = 3) out vec3 out_position;
+layout(location = 4) out vec3 out_to_camera;
+
+layout(set = 0, binding = 0) uniform Camera {
+  mat4 view;
+  mat4 proj;
+  vec4 position;
+} camera;
+
+layout(push_constant) uniform Model {
+  mat4 model;
+} model;
+
+void main() {
+  mat4 instance_model = mat4(instance_model_0, instance_model_1, instance_model_2, instance_model_3);
+  mat4 world = model.model * instance_model;
+  vec4 world_position = world * vec4(position, 1.0);
+
+  gl_Position = camera.proj * camera.view * world_position;
+  out_color = color * instance_color;
+  out_uv = uv;
+  // The inverse transpose keeps normals perpendicular under non-uniform scale.
+  out_normal = mat3(transpose(inverse(world))) * normal;
+  out_position = world_position.xyz;
+  out_to_camera = camera.position.xyz - world_position.xyz;
+}
@@ -0,0 +1,47 @@
+#version 450
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+const float REDUCE_MIN = 1.0 / 128.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float SPAN_MAX = 8.0;
+
+float luma(vec3 color) {
+  return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+// Blurs along the edge through each pixel, found from the luma of its corners.
+void main() {
+  vec2 texel = 1.0 / vec2(textureSize(source, 0));
+  vec4 center = texture(source, uv);
+
+  float nw = luma(texture(source, uv + vec2(-1.0, -1.0) * texel).rgb);
+  float ne = luma(texture(source, uv + vec2(1.0, -1.0) * texel).rgb);
+  float sw = luma(texture(source, uv + vec2(-1.0, 1.0) * texel).rgb);
+  float se = luma(texture(source, uv + vec2(1.0, 1.0) * texel).rgb);
+  float m = luma(center.rgb);
+
+  float min_luma = min(m, min(min(nw, ne), min(sw, se)));
+  float max_luma = max(m, max(max(nw, ne), max(sw, se)));
+
+  vec2 direction = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+  float reduce = max((nw + ne + sw + se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+  float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+  direction = clamp(direction * scale, -SPAN_MAX, SPAN_MAX) * texel;
+
+  vec3 near = 0.5 * (
+    texture(source, uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+    texture(source, uv + direction * (2.0 / 3.0 - 0.5)).rgb
+  );
+  vec3 far = near * 0.5 + 0.25 * (
+    texture(source, uv - direction * 0.5).rgb +
+    texture(source, uv + direction * 0.5).rgb
+  );
+
+  float far_luma = luma(far);
+  vec3 color = far_luma < min_luma || far_luma > max_luma ? near : far;
+  f_color = vec4(color, center.a);
+}
@@ -0,0 +1,16 @@
+#version 450
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 instance_model_0;
+layout(location = 2) in vec4 instance_model_1;
+layout(location = 3) in vec4 instance_model_2;
+layout(location = 4) in vec4 instance_model_3;
+
+layout(push_constant) uniform Shadow {
+  mat4 model;
+  mat4 light;
+} shadow;
+
+void main() {
+  mat4 instance_model = mat4(instance_model_0, instance_model_1, instance_model_2, instance_model_3);
+  gl_Position = shadow.light * shadow.model * instance_model * vec4(position, 1.0);
+}
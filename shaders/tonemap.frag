@@ -0,0 +1,43 @@
+#version 450
+layout(location = 0) in vec2 uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D hdr;
+layout(set = 0, binding = 1) uniform sampler2D bloom;
+
+const int TONEMAP_NONE = 0;
+const int TONEMAP_REINHARD = 1;
+const int TONEMAP_ACES = 2;
+
+layout(push_constant) uniform Tonemap {
+  float exposure;
+  float gamma;
+  float bloom_intensity;
+  float vignette;
+  int mode;
+} tonemap;
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve.
+vec3 aces(vec3 color) {
+  return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+  vec4 source = texture(hdr, uv);
+  vec3 color = source.rgb + texture(bloom, uv).rgb * tonemap.bloom_intensity;
+  color *= tonemap.exposure;
+
+  if (tonemap.mode == TONEMAP_REINHARD) {
+    color = color / (color + 1.0);
+  } else if (tonemap.mode == TONEMAP_ACES) {
+    color = aces(color);
+  }
+
+  // 1 at the center, 1 - vignette in the corners.
+  vec2 offset = (uv - 0.5) * 2.0;
+  color *= 1.0 - tonemap.vignette * dot(offset, offset) * 0.5;
+
+  color = pow(clamp(color, 0.0, 1.0), vec3(1.0 / tonemap.gamma));
+  f_color = vec4(color, clamp(source.a, 0.0, 1.0));
+}
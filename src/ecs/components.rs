@@ -0,0 +1,176 @@
+use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3};
+
+use crate::registry::entity_registry::EntityRegistry;
+
+// Local transform, relative to the entity's parent in `EntityRegistry`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+  pub translation: [f32; 3],
+  pub rotation: [f32; 4],
+  pub scale: [f32; 3],
+}
+
+impl Default for Transform {
+  fn default() -> Self {
+    Self {
+      translation: [0.0, 0.0, 0.0],
+      rotation: [0.0, 0.0, 0.0, 1.0],
+      scale: [1.0, 1.0, 1.0],
+    }
+  }
+}
+
+impl Transform {
+  pub fn matrix(&self) -> Matrix4<f32> {
+    let [x, y, z, w] = self.rotation;
+    let [sx, sy, sz] = self.scale;
+
+    Matrix4::from_translation(Vector3::from(self.translation))
+      * Matrix4::from(Quaternion::new(w, x, y, z))
+      * Matrix4::from_nonuniform_scale(sx, sy, sz)
+  }
+}
+
+// The renderer actors drawing this entity, one per mesh primitive.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshRenderer {
+  pub actors: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Skin {
+  pub joints: Vec<String>,
+  pub inverse_bind_matrices: Vec<[[f32; 4]; 4]>,
+  pub skeleton: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+  Directional,
+  Point,
+  // Angles from the light's axis, in radians. Spots fade out between the two.
+  Spot { inner_angle: f32, outer_angle: f32 },
+}
+
+// Lights sit at the origin of their entity's Transform and shine along its -z
+// axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+  pub kind: LightKind,
+  pub color: [f32; 3],
+  pub intensity: f32,
+  // Distance at which point and spot lights have faded out completely. For
+  // directional lights, the half-size of the box their shadows cover.
+  pub range: f32,
+  // Only directional and spot lights cast shadows.
+  pub casts_shadows: bool,
+}
+
+impl Light {
+  pub fn directional(color: [f32; 3], intensity: f32) -> Self {
+    Self {
+      kind: LightKind::Directional,
+      color,
+      intensity,
+      range: 50.0,
+      casts_shadows: false,
+    }
+  }
+
+  pub fn point(color: [f32; 3], intensity: f32, range: f32) -> Self {
+    Self {
+      kind: LightKind::Point,
+      color,
+      intensity,
+      range,
+      casts_shadows: false,
+    }
+  }
+
+  pub fn spot(
+    color: [f32; 3],
+    intensity: f32,
+    range: f32,
+    inner_angle: f32,
+    outer_angle: f32,
+  ) -> Self {
+    Self {
+      kind: LightKind::Spot {
+        inner_angle,
+        outer_angle,
+      },
+      color,
+      intensity,
+      range,
+      casts_shadows: false,
+    }
+  }
+
+  pub fn with_shadows(mut self) -> Self {
+    self.casts_shadows = true;
+    self
+  }
+
+  pub fn with_range(mut self, range: f32) -> Self {
+    self.range = range;
+    self
+  }
+}
+
+// Combines the entity's Transform with those of its ancestors. Entities without
+// a Transform don't move their children.
+pub fn get_world_matrix(entities: &EntityRegistry, entity: &String) -> Matrix4<f32> {
+  let mut world = Matrix4::identity();
+  for ancestor in entities.get_ancestors(entity).into_iter().rev() {
+    if let Some(transform) = entities.get_component::<Transform>(ancestor) {
+      world = world * transform.matrix();
+    }
+  }
+
+  match entities.get_component::<Transform>(entity) {
+    Some(transform) => world * transform.matrix(),
+    None => world,
+  }
+}
+
+#[cfg(test)]
+mod components_tests {
+  use cgmath::{InnerSpace, Vector4};
+
+  use super::*;
+
+  #[test]
+  fn test_transform_matrix() {
+    assert_eq!(Transform::default().matrix(), Matrix4::identity());
+
+    let half_turn = std::f32::consts::FRAC_1_SQRT_2;
+    let transform = Transform {
+      translation: [1.0, 2.0, 3.0],
+      rotation: [0.0, half_turn, 0.0, half_turn],
+      scale: [2.0, 2.0, 2.0],
+    };
+
+    // Scaled, then turned 90 degrees around y, then moved.
+    let point = transform.matrix() * Vector4::new(1.0, 0.0, 0.0, 1.0);
+    let expected = Vector4::new(1.0, 2.0, 1.0, 1.0);
+    assert!((point - expected).magnitude() < 1e-5);
+  }
+
+  #[test]
+  fn test_world_matrix() {
+    let mut entities = EntityRegistry::new();
+    let parent = String::from("parent");
+    let child = String::from("child");
+
+    let moved = |translation| Transform {
+      translation,
+      ..Default::default()
+    };
+    entities.add_component(parent.clone(), moved([1.0, 0.0, 0.0]));
+    entities.add_component(child.clone(), moved([0.0, 2.0, 0.0]));
+    entities.set_parent(child.clone(), Some(parent));
+
+    let point = get_world_matrix(&entities, &child) * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    assert_eq!(point, Vector4::new(1.0, 2.0, 0.0, 1.0));
+  }
+}
@@ -2,31 +2,83 @@ mod ecs;
 mod registry;
 mod renderer;
 
-use renderer::vulkan::{Vertex, VulkanBackend};
+use registry::{
+  entity_registry::EntityRegistry, event_recorder::SessionMode, event_registry::EventRegistry,
+  task_registry::TaskRegistry, timer_registry::TimerRegistry,
+};
+use renderer::{
+  error::RendererError,
+  light::get_scene_lights,
+  scene::sync_transforms,
+  vulkan::{Vertex, VulkanBackend},
+};
 fn main() {
+  let session = SessionMode::from_args(std::env::args().skip(1)).expect("Invalid arguments");
+
+  let mut events = EventRegistry::new();
+  events
+    .start_session(session)
+    .expect("Failed to start event session");
+  let mut timers = TimerRegistry::new();
+  let mut tasks = TaskRegistry::new();
+  let entities = EntityRegistry::new();
+
   let mut renderer = VulkanBackend::new().expect("Failed to create Vulkan backend");
 
+  load_demo_scene(&mut renderer).expect("Failed to load demo scene");
+
+  // Reloads the built-in shaders whenever their files are saved. The paths are
+  // resolved against the crate, so it works from any working directory.
+  #[cfg(feature = "shader-reload")]
+  {
+    let shaders = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders");
+    if let Err(e) = renderer.load_shader(
+      String::from(crate::renderer::material::DEFAULT_SHADER),
+      shaders.join("default.vert"),
+      shaders.join("default.frag"),
+    ) {
+      println!("Failed to load shaders for hot reload: {}", e);
+    }
+  }
+
+  loop {
+    events.advance_frame();
+    timers.tick(&mut events);
+    tasks.poll();
+
+    if let Err(e) = sync_transforms(&entities, &mut renderer) {
+      println!("Failed to sync entity transforms: {}", e);
+    }
+    renderer.set_lights(get_scene_lights(&entities));
+
+    if renderer.render() {
+      return;
+    }
+  }
+}
+
+fn load_demo_scene(renderer: &mut VulkanBackend) -> Result<(), RendererError> {
   let vertices = vec![
     Vertex {
       position: [0.0, -0.575, 0.0],
       color: [0.0, 0.0, 1.0, 1.0],
+      uv: [0.5, 0.0],
+      normal: [0.0, 0.0, -1.0],
     },
     Vertex {
       position: [-0.6, 0.575, 0.0],
       color: [1.0, 0.0, 0.0, 1.0],
+      uv: [0.0, 1.0],
+      normal: [0.0, 0.0, -1.0],
     },
     Vertex {
       position: [0.6, 0.575, 0.0],
       color: [0.0, 1.0, 0.0, 1.0],
+      uv: [1.0, 1.0],
+      normal: [0.0, 0.0, -1.0],
     },
   ];
 
-  renderer.create_actor(Some(String::from("test_actor")));
-  renderer.upload_model(String::from("test_actor"), vertices);
-
-  loop {
-    if renderer.render() {
-      return;
-    }
-  }
+  let actor = renderer.create_actor(Some(String::from("test_actor")))?;
+  renderer.upload_model(actor, vertices)
 }
@@ -1,2 +1,8 @@
 pub mod entity_registry;
+pub mod event_recorder;
 pub mod event_registry;
+pub mod event_stream;
+#[cfg(feature = "event-tracing")]
+pub mod event_tracer;
+pub mod task_registry;
+pub mod timer_registry;
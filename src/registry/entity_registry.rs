@@ -30,16 +30,27 @@ impl<T: Component> StateQueue for T {
   }
 }
 
-trait Component: Any + Send + Sync {
+pub trait Component: Any + Send + Sync {
   fn as_any(&self) -> &dyn Any;
   fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
+impl<T: Any + Send + Sync> Component for T {
+  fn as_any(&self) -> &dyn Any {
+    self
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
+
 
 #[derive(Default)]
 pub struct EntityRegistry {
   entities: HashMap<(String, TypeId), Box<dyn Component>>,
   components: HashMap<TypeId, HashSet<String>>,
+  parents: HashMap<String, String>,
 }
 
 impl EntityRegistry {
@@ -60,15 +71,17 @@ impl EntityRegistry {
   pub fn get_component<T: Component>(&self, entity: &String) -> Option<&T> {
     let type_id = TypeId::of::<T>();
 
+    // `Box<dyn Component>` is a Component itself, so the box has to be
+    // dereferenced to reach the stored value.
     let entity = self.entities.get(&(entity.clone(), type_id))?;
-    entity.as_ref().downcast_ref::<T>()
+    (**entity).as_any().downcast_ref::<T>()
   }
 
   pub fn get_component_mut<T: Component>(&mut self, entity: &String) -> Option<&mut T> {
     let type_id = TypeId::of::<T>();
 
     let entity = self.entities.get_mut(&(entity.clone(), type_id))?;
-    entity.downcast_mut::<T>()
+    (**entity).as_any_mut().downcast_mut::<T>()
   }
 
   pub fn get_components<T: Component>(&self) -> Option<Vec<&T>> {
@@ -99,6 +112,48 @@ impl EntityRegistry {
 
     Some(set)
   }
+
+  // Drops every component of the entity. Its children keep pointing at it, so
+  // they lose their parent's transform until they're given another one.
+  pub fn remove_entity(&mut self, entity: &String) {
+    self.entities.retain(|(other, _), _| other != entity);
+    for entities in self.components.values_mut() {
+      entities.remove(entity);
+    }
+    self.parents.remove(entity);
+  }
+
+  pub fn set_parent(&mut self, entity: String, parent: Option<String>) {
+    match parent {
+      Some(parent) => {
+        self.parents.insert(entity, parent);
+      }
+      None => {
+        self.parents.remove(&entity);
+      }
+    }
+  }
+
+  pub fn get_parent(&self, entity: &String) -> Option<&String> {
+    self.parents.get(entity)
+  }
+
+  pub fn get_ancestors(&self, entity: &String) -> Vec<&String> {
+    let mut ancestors = Vec::new();
+    let mut visited = HashSet::new();
+    visited.insert(entity);
+
+    let mut current = entity;
+    while let Some(parent) = self.parents.get(current) {
+      if !visited.insert(parent) {
+        break;
+      }
+      ancestors.push(parent);
+      current = parent;
+    }
+
+    ancestors
+  }
 }
 
 #[cfg(test)]
@@ -186,4 +241,45 @@ mod entity_registry_tests {
     assert!(!entities.contains(&String::from("test_entity_3")));
     assert!(!entities.contains(&String::from("test_entity_4")));
   }
+
+  #[test]
+  fn test_remove_entity() {
+    let mut registry = EntityRegistry::new();
+    let entity = String::from("test_entity");
+    let other = String::from("other_entity");
+
+    registry.add_component(entity.clone(), 1);
+    registry.add_component(entity.clone(), 2_i64);
+    registry.add_component(other.clone(), 3);
+    registry.set_parent(entity.clone(), Some(other.clone()));
+
+    registry.remove_entity(&entity);
+    assert_eq!(registry.get_component::<i32>(&entity), None);
+    assert_eq!(registry.get_component::<i64>(&entity), None);
+    assert_eq!(registry.get_parent(&entity), None);
+    assert_eq!(registry.get_entities_by_component::<i32>(), Some(vec![&other]));
+  }
+
+  #[test]
+  fn test_get_ancestors() {
+    let mut registry = EntityRegistry::new();
+
+    registry.set_parent(String::from("child"), Some(String::from("parent")));
+    registry.set_parent(String::from("parent"), Some(String::from("root")));
+
+    assert_eq!(
+      registry.get_parent(&String::from("child")),
+      Some(&String::from("parent"))
+    );
+    assert_eq!(
+      registry.get_ancestors(&String::from("child")),
+      vec![&String::from("parent"), &String::from("root")]
+    );
+
+    registry.set_parent(String::from("root"), Some(String::from("child")));
+    assert_eq!(registry.get_ancestors(&String::from("child")).len(), 2);
+
+    registry.set_parent(String::from("child"), None);
+    assert!(registry.get_ancestors(&String::from("child")).is_empty());
+  }
 }
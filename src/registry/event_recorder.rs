@@ -0,0 +1,155 @@
+use std::{
+  collections::VecDeque,
+  error::Error,
+  fs::File,
+  io::{BufRead, BufReader, BufWriter, Write},
+  path::{Path, PathBuf},
+};
+
+pub enum SessionMode {
+  Live,
+  Record(PathBuf),
+  Replay(PathBuf),
+}
+
+impl SessionMode {
+  pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, Box<dyn Error>> {
+    let mut mode = SessionMode::Live;
+
+    while let Some(arg) = args.next() {
+      let path = match arg.as_str() {
+        "--record" | "--replay" => match args.next() {
+          Some(path) => PathBuf::from(path),
+          None => return Err(format!("{} requires a file path", arg).into()),
+        },
+        _ => return Err(format!("unknown argument: {}", arg).into()),
+      };
+
+      if !matches!(mode, SessionMode::Live) {
+        return Err("--record and --replay can only be given once".into());
+      }
+
+      mode = match arg.as_str() {
+        "--record" => SessionMode::Record(path),
+        _ => SessionMode::Replay(path),
+      };
+    }
+
+    Ok(mode)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedEvent {
+  pub frame: u64,
+  pub name: String,
+  pub payload: String,
+}
+
+pub struct EventRecorder {
+  writer: BufWriter<File>,
+}
+
+impl EventRecorder {
+  pub fn create(path: &Path) -> Result<Self, Box<dyn Error>> {
+    Ok(Self {
+      writer: BufWriter::new(File::create(path)?),
+    })
+  }
+
+  pub fn record(&mut self, frame: u64, name: &str, payload: &str) -> Result<(), Box<dyn Error>> {
+    writeln!(self.writer, "{}\t{}\t{}", frame, name, payload)?;
+    Ok(())
+  }
+
+  pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+    self.writer.flush()?;
+    Ok(())
+  }
+}
+
+pub struct EventReplay {
+  events: VecDeque<RecordedEvent>,
+}
+
+impl EventReplay {
+  pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut events = VecDeque::new();
+
+    for (i, line) in reader.lines().enumerate() {
+      let line = line?;
+      if line.is_empty() {
+        continue;
+      }
+
+      let mut parts = line.splitn(3, '\t');
+      let (frame, name, payload) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(frame), Some(name), Some(payload)) => (frame, name, payload),
+        _ => return Err(format!("malformed event on line {}", i + 1).into()),
+      };
+
+      let frame = frame
+        .parse::<u64>()
+        .map_err(|_| format!("invalid frame number on line {}", i + 1))?;
+
+      events.push_back(RecordedEvent {
+        frame,
+        name: name.to_string(),
+        payload: payload.to_string(),
+      });
+    }
+
+    Ok(Self { events })
+  }
+
+  pub fn drain_frame(&mut self, frame: u64) -> Vec<RecordedEvent> {
+    let mut drained = Vec::new();
+    while let Some(event) = self.events.front() {
+      if event.frame > frame {
+        break;
+      }
+      drained.push(self.events.pop_front().unwrap());
+    }
+    drained
+  }
+
+  pub fn is_finished(&self) -> bool {
+    self.events.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod event_recorder_tests {
+  use super::*;
+
+  fn args(args: &[&str]) -> impl Iterator<Item = String> {
+    args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().into_iter()
+  }
+
+  #[test]
+  fn test_session_mode_from_args() {
+    assert!(matches!(SessionMode::from_args(args(&[])).unwrap(), SessionMode::Live));
+    assert!(matches!(
+      SessionMode::from_args(args(&["--record", "session.events"])).unwrap(),
+      SessionMode::Record(path) if path == PathBuf::from("session.events")
+    ));
+    assert!(matches!(
+      SessionMode::from_args(args(&["--replay", "session.events"])).unwrap(),
+      SessionMode::Replay(path) if path == PathBuf::from("session.events")
+    ));
+    assert!(SessionMode::from_args(args(&["--record"])).is_err());
+    assert!(SessionMode::from_args(args(&["--record", "a", "--replay", "b"])).is_err());
+  }
+
+  #[test]
+  fn test_replay_reports_line_numbers() {
+    let path = std::env::temp_dir().join("isle_event_recorder_malformed.events");
+    std::fs::write(&path, "0\tevent\t{}\nnot-a-frame\tevent\t{}\n").unwrap();
+
+    let error = EventReplay::open(&path).err().unwrap();
+    assert_eq!(error.to_string(), "invalid frame number on line 2");
+
+    std::fs::remove_file(&path).unwrap();
+  }
+}
@@ -1,24 +1,128 @@
-use std::{collections::HashMap, any::{TypeId, Any}, rc::Rc};
+use std::{collections::HashMap, any::{TypeId, Any}, cell::RefCell, rc::Rc, error::Error};
+#[cfg(feature = "event-tracing")]
+use std::{panic::Location, time::Instant};
 
-pub struct EventSubscriptionArgs(pub Box<dyn FnMut(&dyn Any)>, pub Option<Vec<TypeId>>);
+use isle_traits::event::RecordableEvent;
+
+use super::{
+  entity_registry::EntityRegistry,
+  event_recorder::{EventRecorder, EventReplay, SessionMode},
+  event_stream::{self, EventStream, NextEvent},
+};
+#[cfg(feature = "event-tracing")]
+use super::event_tracer::EventTracer;
+
+pub struct EventSubscriptionArgs {
+  callback: Box<dyn FnMut(&dyn Any) -> bool>,
+  filter: Option<Vec<TypeId>>,
+  // Returns true once the subscription can be dropped without waiting for the
+  // next event, e.g. when its stream has gone away.
+  closed: Option<Box<dyn Fn() -> bool>>,
+  #[cfg(feature = "event-tracing")]
+  location: &'static Location<'static>,
+}
 
 impl EventSubscriptionArgs {
-  pub fn invoke(&mut self, event: Rc<Box::<dyn Any>>) {
-    (self.0)(event.as_ref());
+  #[cfg_attr(feature = "event-tracing", track_caller)]
+  pub fn new(callback: Box<dyn FnMut(&dyn Any) -> bool>, filter: Option<Vec<TypeId>>) -> Self {
+    Self {
+      callback,
+      filter,
+      closed: None,
+      #[cfg(feature = "event-tracing")]
+      location: Location::caller(),
+    }
+  }
+
+  fn with_closed(mut self, closed: Option<Box<dyn Fn() -> bool>>) -> Self {
+    self.closed = closed;
+    self
+  }
+
+  pub fn invoke(&mut self, event: Rc<Box::<dyn Any>>) -> bool {
+    (self.callback)(&**event)
+  }
+
+  pub fn filter(&self) -> Option<&Vec<TypeId>> {
+    self.filter.as_ref()
+  }
+
+  pub fn is_closed(&self) -> bool {
+    self.closed.as_ref().map_or(false, |closed| closed())
+  }
+
+  #[cfg(feature = "event-tracing")]
+  pub fn location(&self) -> &'static Location<'static> {
+    self.location
   }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventPhase {
+  Capture,
+  Target,
+  Bubble,
+}
+
+pub struct EventContext {
+  pub target: String,
+  pub current: String,
+  pub phase: EventPhase,
+  propagation_stopped: bool,
+}
+
+impl EventContext {
+  pub fn stop_propagation(&mut self) {
+    self.propagation_stopped = true;
+  }
+
+  pub fn is_propagation_stopped(&self) -> bool {
+    self.propagation_stopped
+  }
+}
+
+pub struct EntityListener {
+  callback: Box<dyn FnMut(&dyn Any, &mut EventContext)>,
+  capture: bool,
+  #[cfg(feature = "event-tracing")]
+  location: &'static Location<'static>,
+}
+
+struct RecordableType {
+  name: &'static str,
+  serialize: fn(&dyn Any) -> Option<String>,
+  deserialize: fn(&str) -> Option<Box<dyn Any>>,
+}
+
 pub struct EventRegistry {
   subscribers: HashMap<TypeId, Vec<EventSubscriptionArgs>>,
+  listeners: HashMap<(String, TypeId), Vec<EntityListener>>,
+  recordable: HashMap<TypeId, RecordableType>,
+  recorder: Option<EventRecorder>,
+  replay: Option<EventReplay>,
+  frame: u64,
+  // Event types with a stream dropped since the last prune.
+  closed_streams: Rc<RefCell<Vec<TypeId>>>,
+  #[cfg(feature = "event-tracing")]
+  tracer: EventTracer,
 }
 
 impl EventRegistry {
   pub fn new() -> Self {
     Self {
       subscribers: HashMap::new(),
+      listeners: HashMap::new(),
+      recordable: HashMap::new(),
+      recorder: None,
+      replay: None,
+      frame: 0,
+      closed_streams: Rc::new(RefCell::new(Vec::new())),
+      #[cfg(feature = "event-tracing")]
+      tracer: EventTracer::new(),
     }
   }
 
+  #[cfg_attr(feature = "event-tracing", track_caller)]
   pub fn subscribe<E>(&mut self, mut callback: impl FnMut(&E) + 'static)
   where
     E: 'static + Any,
@@ -26,44 +130,358 @@ impl EventRegistry {
     self.subscribe_with_filter(callback, None);
   }
 
+  #[cfg_attr(feature = "event-tracing", track_caller)]
   pub fn subscribe_with_filter<E>(&mut self, mut callback: impl FnMut(&E) + 'static, filter: Option<Vec<TypeId>>)
   where
   E: 'static + Any
   {
+    self.subscribe_until_with_filter(
+      move |event: &E| {
+        callback(event);
+        true
+      },
+      filter,
+    );
+  }
+
+  // The subscription is dropped the first time the callback returns false.
+  #[cfg_attr(feature = "event-tracing", track_caller)]
+  pub fn subscribe_until<E>(&mut self, callback: impl FnMut(&E) -> bool + 'static)
+  where
+    E: 'static + Any,
+  {
+    self.subscribe_until_with_filter(callback, None);
+  }
+
+  #[cfg_attr(feature = "event-tracing", track_caller)]
+  pub fn subscribe_until_with_filter<E>(&mut self, callback: impl FnMut(&E) -> bool + 'static, filter: Option<Vec<TypeId>>)
+  where
+    E: 'static + Any,
+  {
+    self.add_subscription(callback, filter, None);
+  }
+
+  #[cfg_attr(feature = "event-tracing", track_caller)]
+  fn add_subscription<E>(
+    &mut self,
+    mut callback: impl FnMut(&E) -> bool + 'static,
+    filter: Option<Vec<TypeId>>,
+    closed: Option<Box<dyn Fn() -> bool>>,
+  )
+  where
+    E: 'static + Any,
+  {
+    #[cfg(feature = "event-tracing")]
+    self.tracer.set_type_name(TypeId::of::<E>(), std::any::type_name::<E>());
+
     let subscribers = self
       .subscribers
       .entry(TypeId::of::<E>())
       .or_insert_with(Vec::new);
 
     let boxed_callback = Box::new(move |event: &dyn Any| {
-      if let Some(event) = event.downcast_ref::<E>() {
-        callback(event);
+      match event.downcast_ref::<E>() {
+        Some(event) => callback(event),
+        None => true,
       }
     });
 
-    subscribers.push(EventSubscriptionArgs(boxed_callback, filter));
+    subscribers.push(EventSubscriptionArgs::new(boxed_callback, filter).with_closed(closed));
+  }
+
+  #[cfg_attr(feature = "event-tracing", track_caller)]
+  pub fn stream<E>(&mut self) -> EventStream<E>
+  where
+    E: 'static + Any + Clone,
+  {
+    let (sender, stream) = event_stream::channel();
+    let closed = sender.clone();
+    self.add_subscription(
+      move |event: &E| sender.send(event.clone()),
+      None,
+      Some(Box::new(move || closed.is_closed())),
+    );
+
+    let closed_streams = self.closed_streams.clone();
+    stream.on_drop(move || closed_streams.borrow_mut().push(TypeId::of::<E>()))
+  }
+
+  // Streams can be dropped while the registry is busy dispatching, so their
+  // subscriptions are removed the next time the registry is used.
+  fn prune_closed_streams(&mut self) {
+    let closed: Vec<TypeId> = self.closed_streams.borrow_mut().drain(..).collect();
+    for type_id in closed {
+      if let Some(subscribers) = self.subscribers.get_mut(&type_id) {
+        subscribers.retain(|subscriber| !subscriber.is_closed());
+      }
+    }
+  }
+
+  #[cfg_attr(feature = "event-tracing", track_caller)]
+  pub fn next<E>(&mut self) -> NextEvent<E>
+  where
+    E: 'static + Any + Clone,
+  {
+    NextEvent::new(self.stream())
   }
 
   pub fn invoke(&mut self, event: Box<dyn Any>)
   {
+    let event_type_id = (*event).type_id();
+    self.record(event_type_id, event.as_ref());
+
+    #[cfg(feature = "event-tracing")]
+    self.tracer.begin_event(event_type_id, self.frame);
+
+    self.notify_subscribers(event);
+  }
+
+  fn notify_subscribers(&mut self, event: Box<dyn Any>) {
+    self.prune_closed_streams();
+
     let event_type_id = (*event).type_id();
     if let Some(subscribers) = self.subscribers.get_mut(&event_type_id) {
       let rc = Rc::new(event);
-      for subscriber in subscribers {
-        subscriber.invoke(rc.clone());
+
+      #[cfg(not(feature = "event-tracing"))]
+      subscribers.retain_mut(|subscriber| subscriber.invoke(rc.clone()));
+
+      #[cfg(feature = "event-tracing")]
+      {
+        let tracer = &mut self.tracer;
+        subscribers.retain_mut(|subscriber| {
+          let started = Instant::now();
+          let keep = subscriber.invoke(rc.clone());
+          tracer.record_handler(event_type_id, subscriber.location, started.elapsed());
+          keep
+        });
       }
     }
   }
 
+  #[cfg(feature = "event-tracing")]
+  pub fn tracer(&self) -> &EventTracer {
+    &self.tracer
+  }
+
+  #[cfg(feature = "event-tracing")]
+  pub fn tracer_mut(&mut self) -> &mut EventTracer {
+    &mut self.tracer
+  }
+
   pub fn get_subscriptions<E: 'static>(&mut self) -> Option<&mut Vec<EventSubscriptionArgs>> {
+    self.prune_closed_streams();
     self.subscribers.get_mut(&TypeId::of::<E>())
   }
+
+  pub fn register_recordable<E>(&mut self)
+  where
+    E: 'static + RecordableEvent,
+  {
+    self.recordable.insert(
+      TypeId::of::<E>(),
+      RecordableType {
+        name: E::event_name(),
+        serialize: |event| event.downcast_ref::<E>()?.serialize_event(),
+        deserialize: |payload| E::deserialize_event(payload).map(|event| Box::new(event) as Box<dyn Any>),
+      },
+    );
+  }
+
+  pub fn start_session(&mut self, mode: SessionMode) -> Result<(), Box<dyn Error>> {
+    self.recorder = None;
+    self.replay = None;
+
+    match mode {
+      SessionMode::Live => {}
+      SessionMode::Record(path) => self.recorder = Some(EventRecorder::create(&path)?),
+      SessionMode::Replay(path) => self.replay = Some(EventReplay::open(&path)?),
+    }
+
+    Ok(())
+  }
+
+  pub fn frame(&self) -> u64 {
+    self.frame
+  }
+
+  // Events recorded before the first call are replayed along with frame 1.
+  pub fn advance_frame(&mut self) {
+    self.frame += 1;
+    self.prune_closed_streams();
+
+    if let Some(recorder) = &mut self.recorder {
+      if let Err(e) = recorder.flush() {
+        println!("Failed to flush event recording: {:?}", e);
+      }
+    }
+
+    let recorded = match &mut self.replay {
+      Some(replay) => replay.drain_frame(self.frame),
+      None => return,
+    };
+
+    for recorded in recorded {
+      let deserialize = self
+        .recordable
+        .values()
+        .find(|recordable| recordable.name == recorded.name)
+        .map(|recordable| recordable.deserialize);
+
+      match deserialize.and_then(|deserialize| deserialize(&recorded.payload)) {
+        Some(event) => self.invoke(event),
+        None => println!("Failed to replay event {} on frame {}", recorded.name, recorded.frame),
+      }
+    }
+  }
+
+  fn record(&mut self, type_id: TypeId, event: &dyn Any) {
+    let (recorder, recordable) = match (&mut self.recorder, self.recordable.get(&type_id)) {
+      (Some(recorder), Some(recordable)) => (recorder, recordable),
+      _ => return,
+    };
+
+    let result = match (recordable.serialize)(event) {
+      Some(payload) => recorder.record(self.frame, recordable.name, &payload),
+      None => Err(format!("couldn't serialize {}", recordable.name).into()),
+    };
+
+    if let Err(e) = result {
+      println!("Failed to record event: {:?}", e);
+    }
+  }
+
+  #[cfg_attr(feature = "event-tracing", track_caller)]
+  pub fn listen<E>(&mut self, entity: String, callback: impl FnMut(&E, &mut EventContext) + 'static)
+  where
+    E: 'static + Any,
+  {
+    self.add_listener(entity, callback, false);
+  }
+
+  #[cfg_attr(feature = "event-tracing", track_caller)]
+  pub fn listen_capture<E>(&mut self, entity: String, callback: impl FnMut(&E, &mut EventContext) + 'static)
+  where
+    E: 'static + Any,
+  {
+    self.add_listener(entity, callback, true);
+  }
+
+  pub fn unlisten<E: 'static>(&mut self, entity: &String) {
+    self.listeners.remove(&(entity.clone(), TypeId::of::<E>()));
+  }
+
+  #[cfg_attr(feature = "event-tracing", track_caller)]
+  fn add_listener<E>(&mut self, entity: String, mut callback: impl FnMut(&E, &mut EventContext) + 'static, capture: bool)
+  where
+    E: 'static + Any,
+  {
+    #[cfg(feature = "event-tracing")]
+    self.tracer.set_type_name(TypeId::of::<E>(), std::any::type_name::<E>());
+
+    let listeners = self
+      .listeners
+      .entry((entity, TypeId::of::<E>()))
+      .or_insert_with(Vec::new);
+
+    let boxed_callback = Box::new(move |event: &dyn Any, context: &mut EventContext| {
+      if let Some(event) = event.downcast_ref::<E>() {
+        callback(event, context);
+      }
+    });
+
+    listeners.push(EntityListener {
+      callback: boxed_callback,
+      capture,
+      #[cfg(feature = "event-tracing")]
+      location: Location::caller(),
+    });
+  }
+
+  // Capture runs root -> target, bubble runs target -> root. Global subscribers
+  // always see the event once propagation has finished.
+  pub fn dispatch<E>(&mut self, entities: &EntityRegistry, target: String, event: E)
+  where
+    E: 'static + Any,
+  {
+    let event_type_id = TypeId::of::<E>();
+    self.record(event_type_id, &event);
+
+    // Listeners and subscribers are traced as handlers of the same event.
+    #[cfg(feature = "event-tracing")]
+    {
+      self.tracer.set_type_name(event_type_id, std::any::type_name::<E>());
+      self.tracer.begin_event(event_type_id, self.frame);
+    }
+
+    let path: Vec<String> = entities
+      .get_ancestors(&target)
+      .into_iter()
+      .rev()
+      .cloned()
+      .collect();
+
+    let mut context = EventContext {
+      target: target.clone(),
+      current: target.clone(),
+      phase: EventPhase::Capture,
+      propagation_stopped: false,
+    };
+
+    'propagation: {
+      for entity in &path {
+        context.current = entity.clone();
+        self.notify_listeners(&event, &mut context, Some(true));
+        if context.propagation_stopped {
+          break 'propagation;
+        }
+      }
+
+      context.phase = EventPhase::Target;
+      context.current = target.clone();
+      self.notify_listeners(&event, &mut context, None);
+      if context.propagation_stopped {
+        break 'propagation;
+      }
+
+      context.phase = EventPhase::Bubble;
+      for entity in path.iter().rev() {
+        context.current = entity.clone();
+        self.notify_listeners(&event, &mut context, Some(false));
+        if context.propagation_stopped {
+          break 'propagation;
+        }
+      }
+    }
+
+    self.notify_subscribers(Box::new(event));
+  }
+
+  fn notify_listeners(&mut self, event: &dyn Any, context: &mut EventContext, capture: Option<bool>) {
+    let key = (context.current.clone(), event.type_id());
+    if let Some(listeners) = self.listeners.get_mut(&key) {
+      for listener in listeners {
+        if capture.map_or(true, |capture| capture == listener.capture) {
+          #[cfg(feature = "event-tracing")]
+          let started = Instant::now();
+
+          (listener.callback)(event, context);
+
+          #[cfg(feature = "event-tracing")]
+          self.tracer.record_handler(key.1, listener.location, started.elapsed());
+        }
+      }
+    }
+  }
 }
 
 #[cfg(test)]
 mod event_registry_tests {
   use std::sync::{Arc, Mutex};
 
+  use isle_macros::Event;
+  use serde::{Deserialize, Serialize};
+
   use crate::filter;
 
 use super::*;
@@ -96,8 +514,113 @@ use super::*;
     let subscriptions = registry.get_subscriptions::<MyEvent>().unwrap();
     assert_eq!(subscriptions.len(), 1);
 
-    let filter = subscriptions[0].1.as_ref().unwrap();
+    let filter = subscriptions[0].filter().unwrap();
     assert_eq!(filter.len(), 2);
     assert_eq!(filter, &filter![i32, i64])
   }
+
+  fn hierarchy() -> EntityRegistry {
+    let mut entities = EntityRegistry::new();
+    entities.set_parent(String::from("button"), Some(String::from("panel")));
+    entities.set_parent(String::from("panel"), Some(String::from("root")));
+    entities
+  }
+
+  fn record(log: &Arc<Mutex<Vec<String>>>, label: &str) -> impl FnMut(&MyEvent, &mut EventContext) + 'static {
+    let log = log.clone();
+    let label = label.to_string();
+    move |_, context| {
+      log.lock().unwrap().push(format!("{}:{}:{:?}", label, context.current, context.phase));
+    }
+  }
+
+  #[test]
+  fn test_dispatch_phases() {
+    let mut registry: EventRegistry = EventRegistry::new();
+    let entities = hierarchy();
+    let log: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+
+    registry.listen_capture(String::from("root"), record(&log, "capture"));
+    registry.listen_capture(String::from("panel"), record(&log, "capture"));
+    registry.listen(String::from("button"), record(&log, "bubble"));
+    registry.listen(String::from("panel"), record(&log, "bubble"));
+    registry.listen(String::from("root"), record(&log, "bubble"));
+    registry.subscribe(record_global(&log));
+
+    registry.dispatch(&entities, String::from("button"), MyEvent {});
+
+    assert_eq!(
+      *log.lock().unwrap(),
+      vec![
+        "capture:root:Capture",
+        "capture:panel:Capture",
+        "bubble:button:Target",
+        "bubble:panel:Bubble",
+        "bubble:root:Bubble",
+        "global",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_dispatch_stop_propagation() {
+    let mut registry: EventRegistry = EventRegistry::new();
+    let entities = hierarchy();
+    let log: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+
+    registry.listen(String::from("button"), record(&log, "bubble"));
+    registry.listen(String::from("panel"), |_: &MyEvent, context| context.stop_propagation());
+    registry.listen(String::from("root"), record(&log, "bubble"));
+    registry.subscribe(record_global(&log));
+
+    registry.dispatch(&entities, String::from("button"), MyEvent {});
+
+    assert_eq!(*log.lock().unwrap(), vec!["bubble:button:Target", "global"]);
+  }
+
+  fn record_global(log: &Arc<Mutex<Vec<String>>>) -> impl FnMut(&MyEvent) + 'static {
+    let log = log.clone();
+    move |_| log.lock().unwrap().push(String::from("global"))
+  }
+
+  #[derive(Event, Serialize, Deserialize, Debug, PartialEq)]
+  #[event(record)]
+  struct KeyPressed {
+    key: String,
+  }
+
+  #[test]
+  fn test_record_and_replay() {
+    let path = std::env::temp_dir().join("isle_event_registry_replay.events");
+
+    let mut recording: EventRegistry = EventRegistry::new();
+    recording.register_recordable::<KeyPressed>();
+    recording.start_session(SessionMode::Record(path.clone())).unwrap();
+    recording.advance_frame();
+    recording.invoke(Box::new(KeyPressed { key: String::from("w") }));
+    recording.invoke(Box::new(MyEvent {}));
+    recording.advance_frame();
+    recording.advance_frame();
+    recording.invoke(Box::new(KeyPressed { key: String::from("s") }));
+    recording.start_session(SessionMode::Live).unwrap();
+
+    let mut replaying: EventRegistry = EventRegistry::new();
+    let replayed: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+    let r_ref = replayed.clone();
+    replaying.register_recordable::<KeyPressed>();
+    replaying.start_session(SessionMode::Replay(path.clone())).unwrap();
+    replaying.subscribe(move |event: &KeyPressed| {
+      r_ref.lock().unwrap().push(event.key.clone());
+    });
+
+    let mut frames = Vec::new();
+    for _ in 0..3 {
+      replaying.advance_frame();
+      frames.push(replayed.lock().unwrap().drain(..).collect::<Vec<_>>());
+    }
+
+    assert_eq!(frames, vec![vec!["w"], vec![], vec!["s"]]);
+
+    std::fs::remove_file(&path).unwrap();
+  }
 }
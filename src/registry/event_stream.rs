@@ -0,0 +1,122 @@
+use std::{
+  cell::RefCell,
+  collections::VecDeque,
+  future::Future,
+  pin::Pin,
+  rc::{Rc, Weak},
+  task::{Context, Poll, Waker},
+};
+
+use futures::Stream;
+
+struct Channel<E> {
+  queue: VecDeque<E>,
+  waker: Option<Waker>,
+}
+
+pub fn channel<E>() -> (EventSender<E>, EventStream<E>) {
+  let channel = Rc::new(RefCell::new(Channel {
+    queue: VecDeque::new(),
+    waker: None,
+  }));
+
+  (
+    EventSender {
+      channel: Rc::downgrade(&channel),
+    },
+    EventStream {
+      channel,
+      on_drop: None,
+    },
+  )
+}
+
+pub struct EventSender<E> {
+  channel: Weak<RefCell<Channel<E>>>,
+}
+
+impl<E> EventSender<E> {
+  pub fn is_closed(&self) -> bool {
+    self.channel.strong_count() == 0
+  }
+
+  pub fn send(&self, event: E) -> bool {
+    let channel = match self.channel.upgrade() {
+      Some(channel) => channel,
+      None => return false,
+    };
+
+    let mut channel = channel.borrow_mut();
+    channel.queue.push_back(event);
+    if let Some(waker) = channel.waker.take() {
+      waker.wake();
+    }
+
+    true
+  }
+}
+
+impl<E> Clone for EventSender<E> {
+  fn clone(&self) -> Self {
+    Self {
+      channel: self.channel.clone(),
+    }
+  }
+}
+
+pub struct EventStream<E> {
+  channel: Rc<RefCell<Channel<E>>>,
+  on_drop: Option<Box<dyn FnOnce()>>,
+}
+
+impl<E> EventStream<E> {
+  // Runs `callback` once the stream is dropped, so its sender can be cleaned up.
+  pub fn on_drop(mut self, callback: impl FnOnce() + 'static) -> Self {
+    self.on_drop = Some(Box::new(callback));
+    self
+  }
+}
+
+impl<E> Drop for EventStream<E> {
+  fn drop(&mut self) {
+    if let Some(callback) = self.on_drop.take() {
+      callback();
+    }
+  }
+}
+
+impl<E> Stream for EventStream<E> {
+  type Item = E;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<E>> {
+    let mut channel = self.channel.borrow_mut();
+    match channel.queue.pop_front() {
+      Some(event) => Poll::Ready(Some(event)),
+      None => {
+        channel.waker = Some(cx.waker().clone());
+        Poll::Pending
+      }
+    }
+  }
+}
+
+pub struct NextEvent<E> {
+  stream: EventStream<E>,
+}
+
+impl<E> NextEvent<E> {
+  pub fn new(stream: EventStream<E>) -> Self {
+    Self { stream }
+  }
+}
+
+impl<E> Future for NextEvent<E> {
+  type Output = E;
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<E> {
+    match Pin::new(&mut self.stream).poll_next(cx) {
+      Poll::Ready(Some(event)) => Poll::Ready(event),
+      _ => Poll::Pending,
+    }
+  }
+}
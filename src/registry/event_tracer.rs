@@ -0,0 +1,271 @@
+use std::{
+  any::TypeId,
+  collections::{HashMap, VecDeque},
+  error::Error,
+  panic::Location,
+  path::Path,
+  time::Duration,
+};
+
+use serde::Serialize;
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct EventTypeStats {
+  pub name: String,
+  pub count: u64,
+  pub handler_nanos: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SubscriberStats {
+  pub event: String,
+  pub subscriber: String,
+  pub calls: u64,
+  pub total_nanos: u64,
+  pub max_nanos: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct EventFlowEntry {
+  pub event: String,
+  pub handlers: u32,
+  pub handler_nanos: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FrameTrace {
+  pub frame: u64,
+  pub events: Vec<EventFlowEntry>,
+}
+
+#[derive(Serialize)]
+struct TraceDump<'a> {
+  event_types: Vec<&'a EventTypeStats>,
+  subscribers: Vec<&'a SubscriberStats>,
+  frames: &'a VecDeque<FrameTrace>,
+}
+
+pub struct EventTracer {
+  type_names: HashMap<TypeId, &'static str>,
+  types: HashMap<TypeId, EventTypeStats>,
+  subscribers: HashMap<(TypeId, &'static Location<'static>), SubscriberStats>,
+  frames: VecDeque<FrameTrace>,
+  max_frames: usize,
+}
+
+impl EventTracer {
+  pub fn new() -> Self {
+    Self {
+      type_names: HashMap::new(),
+      types: HashMap::new(),
+      subscribers: HashMap::new(),
+      frames: VecDeque::new(),
+      max_frames: 120,
+    }
+  }
+
+  pub fn set_max_frames(&mut self, max_frames: usize) {
+    self.max_frames = max_frames;
+    self.trim_frames();
+  }
+
+  pub fn set_type_name(&mut self, type_id: TypeId, name: &'static str) {
+    self.type_names.insert(type_id, name);
+  }
+
+  pub fn begin_event(&mut self, type_id: TypeId, frame: u64) {
+    let name = self.type_name(type_id);
+
+    let stats = self.types.entry(type_id).or_insert_with(|| EventTypeStats {
+      name: name.to_string(),
+      ..Default::default()
+    });
+    stats.count += 1;
+
+    if self.frames.back().map_or(true, |trace| trace.frame != frame) {
+      self.frames.push_back(FrameTrace {
+        frame,
+        events: Vec::new(),
+      });
+      self.trim_frames();
+    }
+
+    if let Some(trace) = self.frames.back_mut() {
+      trace.events.push(EventFlowEntry {
+        event: name.to_string(),
+        handlers: 0,
+        handler_nanos: 0,
+      });
+    }
+  }
+
+  pub fn record_handler(&mut self, type_id: TypeId, subscriber: &'static Location<'static>, elapsed: Duration) {
+    let nanos = elapsed.as_nanos() as u64;
+    let name = self.type_name(type_id);
+
+    if let Some(stats) = self.types.get_mut(&type_id) {
+      stats.handler_nanos += nanos;
+    }
+
+    let stats = self
+      .subscribers
+      .entry((type_id, subscriber))
+      .or_insert_with(|| SubscriberStats {
+        event: name.to_string(),
+        subscriber: subscriber.to_string(),
+        ..Default::default()
+      });
+    stats.calls += 1;
+    stats.total_nanos += nanos;
+    stats.max_nanos = stats.max_nanos.max(nanos);
+
+    if let Some(entry) = self.frames.back_mut().and_then(|trace| trace.events.last_mut()) {
+      entry.handlers += 1;
+      entry.handler_nanos += nanos;
+    }
+  }
+
+  pub fn event_stats(&self, type_id: TypeId) -> Option<&EventTypeStats> {
+    self.types.get(&type_id)
+  }
+
+  pub fn slowest_subscribers(&self, count: usize) -> Vec<&SubscriberStats> {
+    let mut subscribers: Vec<&SubscriberStats> = self.subscribers.values().collect();
+    subscribers.sort_by(|a, b| b.max_nanos.cmp(&a.max_nanos));
+    subscribers.truncate(count);
+    subscribers
+  }
+
+  pub fn frame(&self, frame: u64) -> Option<&FrameTrace> {
+    self.frames.iter().find(|trace| trace.frame == frame)
+  }
+
+  pub fn clear(&mut self) {
+    self.types.clear();
+    self.subscribers.clear();
+    self.frames.clear();
+  }
+
+  pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+    let mut event_types: Vec<&EventTypeStats> = self.types.values().collect();
+    event_types.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let dump = TraceDump {
+      event_types,
+      subscribers: self.slowest_subscribers(self.subscribers.len()),
+      frames: &self.frames,
+    };
+
+    Ok(serde_json::to_string_pretty(&dump)?)
+  }
+
+  pub fn dump_json(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+    std::fs::write(path, self.to_json()?)?;
+    Ok(())
+  }
+
+  fn type_name(&self, type_id: TypeId) -> &'static str {
+    self.type_names.get(&type_id).copied().unwrap_or("<unknown>")
+  }
+
+  fn trim_frames(&mut self) {
+    while self.frames.len() > self.max_frames {
+      self.frames.pop_front();
+    }
+  }
+}
+
+#[cfg(test)]
+mod event_tracer_tests {
+  use super::*;
+  use crate::registry::{entity_registry::EntityRegistry, event_registry::EventRegistry};
+
+  struct Ping {}
+  struct Pong {}
+
+  #[test]
+  fn test_event_counts_and_frames() {
+    let mut registry = EventRegistry::new();
+
+    registry.subscribe(|_: &Ping| {});
+    registry.subscribe(|_: &Ping| {});
+    registry.subscribe(|_: &Pong| {});
+
+    registry.invoke(Box::new(Ping {}));
+    registry.advance_frame();
+    registry.invoke(Box::new(Ping {}));
+    registry.invoke(Box::new(Pong {}));
+
+    let tracer = registry.tracer();
+    let ping = tracer.event_stats(TypeId::of::<Ping>()).unwrap();
+    assert_eq!(ping.count, 2);
+    assert_eq!(tracer.event_stats(TypeId::of::<Pong>()).unwrap().count, 1);
+
+    let subscribers = tracer.slowest_subscribers(usize::MAX);
+    assert_eq!(subscribers.len(), 3);
+    assert!(subscribers
+      .windows(2)
+      .all(|pair| pair[0].max_nanos >= pair[1].max_nanos));
+
+    let pings: Vec<_> = subscribers
+      .iter()
+      .filter(|stats| stats.event.ends_with("Ping"))
+      .collect();
+    assert_eq!(pings.len(), 2);
+    assert!(pings.iter().all(|stats| stats.calls == 2));
+    let nanos: u64 = pings.iter().map(|stats| stats.total_nanos).sum();
+    assert_eq!(ping.handler_nanos, nanos);
+
+    let frame = tracer.frame(1).unwrap();
+    assert_eq!(frame.events.len(), 2);
+    assert_eq!(frame.events[0].handlers, 2);
+    assert!(frame.events[1].event.ends_with("Pong"));
+
+    let json = tracer.to_json().unwrap();
+    assert!(json.contains("\"event_types\""));
+    assert!(json.contains("\"frames\""));
+  }
+
+  #[test]
+  fn test_slowest_subscribers() {
+    let mut tracer = EventTracer::new();
+    let fast = Location::caller();
+    let slow = Location::caller();
+    let type_id = TypeId::of::<Ping>();
+
+    tracer.begin_event(type_id, 0);
+    tracer.record_handler(type_id, fast, Duration::from_nanos(20));
+    tracer.record_handler(type_id, slow, Duration::from_nanos(50));
+    tracer.begin_event(type_id, 0);
+    tracer.record_handler(type_id, fast, Duration::from_nanos(10));
+    tracer.record_handler(type_id, slow, Duration::from_nanos(30));
+
+    let slowest = tracer.slowest_subscribers(2);
+    assert_eq!(slowest[0].subscriber, slow.to_string());
+    assert_eq!(slowest[0].max_nanos, 50);
+    assert_eq!(slowest[0].total_nanos, 80);
+    assert_eq!(slowest[1].calls, 2);
+    assert_eq!(slowest[1].max_nanos, 20);
+    assert_eq!(tracer.event_stats(type_id).unwrap().handler_nanos, 110);
+    assert_eq!(tracer.frame(0).unwrap().events[1].handler_nanos, 40);
+  }
+
+  #[test]
+  fn test_dispatch_listeners() {
+    let mut registry = EventRegistry::new();
+    let entities = EntityRegistry::new();
+    let target = String::from("target");
+
+    registry.listen(target.clone(), |_: &Ping, _| {});
+    registry.subscribe(|_: &Ping| {});
+    registry.dispatch(&entities, target, Ping {});
+
+    let tracer = registry.tracer();
+    assert_eq!(tracer.event_stats(TypeId::of::<Ping>()).unwrap().count, 1);
+    assert_eq!(tracer.slowest_subscribers(2).len(), 2);
+
+    let frame = tracer.frame(0).unwrap();
+    assert_eq!(frame.events.len(), 1);
+    assert_eq!(frame.events[0].handlers, 2);
+  }
+}
@@ -0,0 +1,196 @@
+use std::{
+  cell::RefCell,
+  future::Future,
+  pin::Pin,
+  rc::Rc,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  task::{Context, Wake, Waker},
+};
+
+struct TaskWaker {
+  woken: AtomicBool,
+}
+
+impl Wake for TaskWaker {
+  fn wake(self: Arc<Self>) {
+    self.woken.store(true, Ordering::Release);
+  }
+
+  fn wake_by_ref(self: &Arc<Self>) {
+    self.woken.store(true, Ordering::Release);
+  }
+}
+
+struct Task {
+  future: Pin<Box<dyn Future<Output = ()>>>,
+  waker: Arc<TaskWaker>,
+}
+
+#[derive(Clone, Default)]
+pub struct TaskSpawner {
+  spawned: Rc<RefCell<Vec<Task>>>,
+}
+
+impl TaskSpawner {
+  pub fn spawn(&self, future: impl Future<Output = ()> + 'static) {
+    self.spawned.borrow_mut().push(Task {
+      future: Box::pin(future),
+      waker: Arc::new(TaskWaker {
+        woken: AtomicBool::new(true),
+      }),
+    });
+  }
+}
+
+// Single-threaded executor, polled once per frame after events and timers
+// have been dispatched. Only tasks woken since the last poll are polled again.
+#[derive(Default)]
+pub struct TaskRegistry {
+  tasks: Vec<Task>,
+  spawner: TaskSpawner,
+}
+
+impl TaskRegistry {
+  pub fn new() -> Self {
+    Self {
+      ..Default::default()
+    }
+  }
+
+  pub fn spawner(&self) -> TaskSpawner {
+    self.spawner.clone()
+  }
+
+  pub fn spawn(&self, future: impl Future<Output = ()> + 'static) {
+    self.spawner.spawn(future);
+  }
+
+  pub fn len(&self) -> usize {
+    self.tasks.len() + self.spawner.spawned.borrow().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn poll(&mut self) {
+    self.tasks.append(&mut self.spawner.spawned.borrow_mut());
+
+    self.tasks.retain_mut(|task| {
+      if !task.waker.woken.swap(false, Ordering::AcqRel) {
+        return true;
+      }
+
+      let waker = Waker::from(task.waker.clone());
+      let mut cx = Context::from_waker(&waker);
+      task.future.as_mut().poll(&mut cx).is_pending()
+    });
+  }
+}
+
+#[cfg(test)]
+mod task_registry_tests {
+  use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+  };
+
+  use futures::StreamExt;
+
+  use super::*;
+  use crate::registry::{
+    event_registry::EventRegistry,
+    timer_registry::{MockClock, TimerRegistry},
+  };
+
+  #[derive(Clone)]
+  struct Line(&'static str);
+
+  #[test]
+  fn test_await_events_and_timers() {
+    let clock = MockClock::new();
+    let mut timers = TimerRegistry::with_clock(clock.clone());
+    let mut events = EventRegistry::new();
+    let mut tasks = TaskRegistry::new();
+    let log: Arc<Mutex<Vec<&'static str>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let next = events.next::<Line>();
+    let sleep = timers.sleep(Duration::from_secs(1));
+    let l_ref = log.clone();
+    tasks.spawn(async move {
+      let line = next.await;
+      l_ref.lock().unwrap().push(line.0);
+      sleep.await.unwrap();
+      l_ref.lock().unwrap().push("after sleep");
+    });
+
+    tasks.poll();
+    assert!(log.lock().unwrap().is_empty());
+
+    events.invoke(Box::new(Line("hello")));
+    events.invoke(Box::new(Line("ignored")));
+    tasks.poll();
+    assert_eq!(*log.lock().unwrap(), vec!["hello"]);
+
+    clock.advance(Duration::from_secs(1));
+    timers.tick(&mut events);
+    tasks.poll();
+    assert_eq!(*log.lock().unwrap(), vec!["hello", "after sleep"]);
+    assert!(tasks.is_empty());
+  }
+
+  #[test]
+  fn test_cancel_sleep() {
+    let mut timers = TimerRegistry::with_clock(MockClock::new());
+    let mut tasks = TaskRegistry::new();
+    let log: Arc<Mutex<Vec<bool>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let sleep = timers.sleep(Duration::from_secs(1));
+    let handle = sleep.handle();
+    let l_ref = log.clone();
+    tasks.spawn(async move {
+      l_ref.lock().unwrap().push(sleep.await.is_ok());
+    });
+
+    tasks.poll();
+    assert!(timers.cancel(handle));
+    tasks.poll();
+    assert_eq!(*log.lock().unwrap(), vec![false]);
+    assert!(tasks.is_empty());
+  }
+
+  #[test]
+  fn test_event_stream() {
+    let mut events = EventRegistry::new();
+    let mut tasks = TaskRegistry::new();
+    let log: Arc<Mutex<Vec<&'static str>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let stream = events.stream::<Line>();
+    let l_ref = log.clone();
+    tasks.spawn(async move {
+      let lines: Vec<Line> = stream.take(2).collect().await;
+      l_ref.lock().unwrap().extend(lines.into_iter().map(|line| line.0));
+    });
+
+    events.invoke(Box::new(Line("one")));
+    tasks.poll();
+    assert!(log.lock().unwrap().is_empty());
+
+    events.invoke(Box::new(Line("two")));
+    events.invoke(Box::new(Line("three")));
+    tasks.poll();
+    assert_eq!(*log.lock().unwrap(), vec!["one", "two"]);
+    assert!(tasks.is_empty());
+
+    // The stream went away with the finished task.
+    assert!(events.get_subscriptions::<Line>().unwrap().is_empty());
+
+    let stream = events.stream::<Line>();
+    assert_eq!(events.get_subscriptions::<Line>().unwrap().len(), 1);
+    drop(stream);
+    assert!(events.get_subscriptions::<Line>().unwrap().is_empty());
+  }
+}
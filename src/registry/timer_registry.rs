@@ -0,0 +1,444 @@
+use std::{
+  any::Any,
+  cell::{Cell, RefCell},
+  error::Error,
+  fmt,
+  future::Future,
+  pin::Pin,
+  rc::{Rc, Weak},
+  task::{Context, Poll, Waker},
+  time::{Duration, Instant, TryFromFloatSecsError},
+};
+
+use super::event_registry::EventRegistry;
+
+pub trait Clock {
+  fn now(&self) -> Duration;
+}
+
+pub struct SystemClock {
+  start: Instant,
+}
+
+impl SystemClock {
+  pub fn new() -> Self {
+    Self {
+      start: Instant::now(),
+    }
+  }
+}
+
+impl Clock for SystemClock {
+  fn now(&self) -> Duration {
+    self.start.elapsed()
+  }
+}
+
+#[derive(Clone, Default)]
+pub struct MockClock {
+  now: Rc<Cell<Duration>>,
+}
+
+impl MockClock {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn advance(&self, by: Duration) {
+    self.now.set(self.now.get() + by);
+  }
+}
+
+impl Clock for MockClock {
+  fn now(&self) -> Duration {
+    self.now.get()
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameTime {
+  pub frame: u64,
+  pub delta: Duration,
+  pub elapsed: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimerHandle(u64);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Delay {
+  Time(Duration),
+  Frames(u64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+enum Due {
+  Time(Duration),
+  Frame(u64),
+}
+
+enum TimerAction {
+  Event(Box<dyn Fn() -> Box<dyn Any>>),
+  Wake(Weak<RefCell<SleepState>>),
+}
+
+struct Timer {
+  handle: TimerHandle,
+  due: Due,
+  interval: Option<Delay>,
+  action: TimerAction,
+}
+
+#[derive(Default)]
+struct SleepState {
+  elapsed: bool,
+  cancelled: bool,
+  waker: Option<Waker>,
+}
+
+// A `Sleep` resolves to this when its timer is cancelled before it's due.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SleepCancelled;
+
+impl fmt::Display for SleepCancelled {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Sleep timer was cancelled")
+  }
+}
+
+impl Error for SleepCancelled {}
+
+pub struct Sleep {
+  handle: TimerHandle,
+  state: Rc<RefCell<SleepState>>,
+}
+
+impl Sleep {
+  pub fn handle(&self) -> TimerHandle {
+    self.handle
+  }
+}
+
+impl Future for Sleep {
+  type Output = Result<(), SleepCancelled>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let mut state = self.state.borrow_mut();
+    if state.elapsed {
+      return Poll::Ready(Ok(()));
+    }
+    if state.cancelled {
+      return Poll::Ready(Err(SleepCancelled));
+    }
+
+    state.waker = Some(cx.waker().clone());
+    Poll::Pending
+  }
+}
+
+pub struct TimerRegistry {
+  clock: Box<dyn Clock>,
+  frame_time: FrameTime,
+  timers: Vec<Timer>,
+  next_handle: u64,
+}
+
+impl TimerRegistry {
+  pub fn new() -> Self {
+    Self::with_clock(SystemClock::new())
+  }
+
+  pub fn with_clock(clock: impl Clock + 'static) -> Self {
+    let elapsed = clock.now();
+    Self {
+      clock: Box::new(clock),
+      frame_time: FrameTime {
+        elapsed,
+        ..Default::default()
+      },
+      timers: Vec::new(),
+      next_handle: 0,
+    }
+  }
+
+  pub fn frame_time(&self) -> &FrameTime {
+    &self.frame_time
+  }
+
+  pub fn after<E>(&mut self, delay: Duration, event: E) -> TimerHandle
+  where
+    E: 'static + Any + Clone,
+  {
+    self.schedule(Delay::Time(delay), None, event)
+  }
+
+  // Fails on negative, non-finite or overflowing `seconds`.
+  pub fn after_seconds<E>(
+    &mut self,
+    seconds: f32,
+    event: E,
+  ) -> Result<TimerHandle, TryFromFloatSecsError>
+  where
+    E: 'static + Any + Clone,
+  {
+    Ok(self.after(Duration::try_from_secs_f32(seconds)?, event))
+  }
+
+  pub fn after_frames<E>(&mut self, frames: u64, event: E) -> TimerHandle
+  where
+    E: 'static + Any + Clone,
+  {
+    self.schedule(Delay::Frames(frames), None, event)
+  }
+
+  pub fn every<E>(&mut self, interval: Duration, event: E) -> TimerHandle
+  where
+    E: 'static + Any + Clone,
+  {
+    self.schedule(Delay::Time(interval), Some(Delay::Time(interval)), event)
+  }
+
+  // Fails on negative, non-finite or overflowing `seconds`.
+  pub fn every_seconds<E>(
+    &mut self,
+    seconds: f32,
+    event: E,
+  ) -> Result<TimerHandle, TryFromFloatSecsError>
+  where
+    E: 'static + Any + Clone,
+  {
+    Ok(self.every(Duration::try_from_secs_f32(seconds)?, event))
+  }
+
+  pub fn every_frames<E>(&mut self, frames: u64, event: E) -> TimerHandle
+  where
+    E: 'static + Any + Clone,
+  {
+    self.schedule(Delay::Frames(frames), Some(Delay::Frames(frames)), event)
+  }
+
+  pub fn sleep(&mut self, delay: Duration) -> Sleep {
+    self.sleep_with(Delay::Time(delay))
+  }
+
+  pub fn sleep_frames(&mut self, frames: u64) -> Sleep {
+    self.sleep_with(Delay::Frames(frames))
+  }
+
+  fn sleep_with(&mut self, delay: Delay) -> Sleep {
+    let state = Rc::new(RefCell::new(SleepState::default()));
+    let handle = self.push_timer(delay, None, TimerAction::Wake(Rc::downgrade(&state)));
+    Sleep { handle, state }
+  }
+
+  pub fn cancel(&mut self, handle: TimerHandle) -> bool {
+    let index = match self.timers.iter().position(|timer| timer.handle == handle) {
+      Some(index) => index,
+      None => return false,
+    };
+
+    if let TimerAction::Wake(state) = self.timers.remove(index).action {
+      if let Some(state) = state.upgrade() {
+        let mut state = state.borrow_mut();
+        state.cancelled = true;
+        if let Some(waker) = state.waker.take() {
+          waker.wake();
+        }
+      }
+    }
+
+    true
+  }
+
+  pub fn is_scheduled(&self, handle: TimerHandle) -> bool {
+    self.timers.iter().any(|timer| timer.handle == handle)
+  }
+
+  // Frame timers follow `events.frame()`, so call this after
+  // `EventRegistry::advance_frame`.
+  pub fn tick(&mut self, events: &mut EventRegistry) {
+    let now = self.clock.now();
+    self.frame_time = FrameTime {
+      frame: events.frame(),
+      delta: now.saturating_sub(self.frame_time.elapsed),
+      elapsed: now,
+    };
+
+    let mut due: Vec<(Due, TimerHandle)> = self
+      .timers
+      .iter()
+      .filter(|timer| self.is_due(timer.due))
+      .map(|timer| (timer.due, timer.handle))
+      .collect();
+    due.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    for (_, handle) in due {
+      let index = match self.timers.iter().position(|timer| timer.handle == handle) {
+        Some(index) => index,
+        None => continue,
+      };
+
+      match &self.timers[index].action {
+        TimerAction::Event(event) => events.invoke(event()),
+        TimerAction::Wake(state) => {
+          if let Some(state) = state.upgrade() {
+            let mut state = state.borrow_mut();
+            state.elapsed = true;
+            if let Some(waker) = state.waker.take() {
+              waker.wake();
+            }
+          }
+        }
+      }
+
+      match self.timers[index].interval {
+        Some(interval) => {
+          let timer = &mut self.timers[index];
+          timer.due = match (timer.due, interval) {
+            (Due::Time(due), Delay::Time(interval)) => Due::Time(due + interval),
+            (Due::Frame(due), Delay::Frames(interval)) => Due::Frame(due + interval.max(1)),
+            (_, interval) => self.frame_time.due_after(interval),
+          };
+        }
+        None => {
+          self.timers.remove(index);
+        }
+      }
+    }
+  }
+
+  fn schedule<E>(&mut self, delay: Delay, interval: Option<Delay>, event: E) -> TimerHandle
+  where
+    E: 'static + Any + Clone,
+  {
+    let action = TimerAction::Event(Box::new(move || Box::new(event.clone()) as Box<dyn Any>));
+    self.push_timer(delay, interval, action)
+  }
+
+  fn push_timer(
+    &mut self,
+    delay: Delay,
+    interval: Option<Delay>,
+    action: TimerAction,
+  ) -> TimerHandle {
+    let handle = TimerHandle(self.next_handle);
+    self.next_handle += 1;
+
+    self.timers.push(Timer {
+      handle,
+      due: self.frame_time.due_after(delay),
+      interval,
+      action,
+    });
+
+    handle
+  }
+
+  fn is_due(&self, due: Due) -> bool {
+    match due {
+      Due::Time(due) => due <= self.frame_time.elapsed,
+      Due::Frame(due) => due <= self.frame_time.frame,
+    }
+  }
+}
+
+impl FrameTime {
+  fn due_after(&self, delay: Delay) -> Due {
+    match delay {
+      Delay::Time(delay) => Due::Time(self.elapsed + delay),
+      Delay::Frames(frames) => Due::Frame(self.frame + frames.max(1)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod timer_registry_tests {
+  use std::sync::{Arc, Mutex};
+
+  use super::*;
+
+  #[derive(Clone)]
+  struct Tick(u32);
+
+  fn collect(events: &mut EventRegistry) -> Arc<Mutex<Vec<u32>>> {
+    let fired: Arc<Mutex<Vec<u32>>> = Arc::new(Mutex::new(Vec::new()));
+    let f_ref = fired.clone();
+    events.subscribe(move |tick: &Tick| f_ref.lock().unwrap().push(tick.0));
+    fired
+  }
+
+  #[test]
+  fn test_after_seconds() {
+    let clock = MockClock::new();
+    let mut timers = TimerRegistry::with_clock(clock.clone());
+    let mut events = EventRegistry::new();
+    let fired = collect(&mut events);
+
+    timers.after_seconds(1.0, Tick(1)).unwrap();
+    assert!(timers.after_seconds(-1.0, Tick(1)).is_err());
+    assert!(timers.every_seconds(f32::NAN, Tick(1)).is_err());
+
+    clock.advance(Duration::from_millis(500));
+    timers.tick(&mut events);
+    assert!(fired.lock().unwrap().is_empty());
+    assert_eq!(timers.frame_time().delta, Duration::from_millis(500));
+
+    clock.advance(Duration::from_millis(500));
+    timers.tick(&mut events);
+    timers.tick(&mut events);
+    assert_eq!(*fired.lock().unwrap(), vec![1]);
+  }
+
+  #[test]
+  fn test_after_frames_and_ordering() {
+    let clock = MockClock::new();
+    let mut timers = TimerRegistry::with_clock(clock.clone());
+    let mut events = EventRegistry::new();
+    let fired = collect(&mut events);
+
+    timers.after_frames(2, Tick(2));
+    timers.after_frames(1, Tick(1));
+
+    events.advance_frame();
+    timers.tick(&mut events);
+    assert_eq!(*fired.lock().unwrap(), vec![1]);
+
+    // Ticking twice in one frame doesn't advance frame timers.
+    timers.tick(&mut events);
+    assert_eq!(*fired.lock().unwrap(), vec![1]);
+
+    events.advance_frame();
+    timers.tick(&mut events);
+    assert_eq!(*fired.lock().unwrap(), vec![1, 2]);
+  }
+
+  #[test]
+  fn test_every_and_cancel() {
+    let clock = MockClock::new();
+    let mut timers = TimerRegistry::with_clock(clock.clone());
+    let mut events = EventRegistry::new();
+    let fired = collect(&mut events);
+
+    let handle = timers.every(Duration::from_millis(100), Tick(1));
+    timers.every_frames(2, Tick(2));
+
+    for _ in 0..4 {
+      clock.advance(Duration::from_millis(100));
+      events.advance_frame();
+      timers.tick(&mut events);
+    }
+    assert_eq!(*fired.lock().unwrap(), vec![1, 1, 2, 1, 1, 2]);
+
+    assert!(timers.cancel(handle));
+    assert!(!timers.is_scheduled(handle));
+    assert!(!timers.cancel(handle));
+
+    fired.lock().unwrap().clear();
+    for _ in 0..2 {
+      clock.advance(Duration::from_millis(100));
+      events.advance_frame();
+      timers.tick(&mut events);
+    }
+    assert_eq!(*fired.lock().unwrap(), vec![2]);
+  }
+}
@@ -1 +1,17 @@
+pub mod camera;
+pub mod error;
+#[cfg(test)]
+pub mod golden;
+pub mod graph;
+pub mod light;
+pub mod material;
+pub mod mesh;
+pub mod obj;
+pub mod post;
+pub mod scene;
+#[cfg(feature = "shader-reload")]
+pub mod shader;
+pub mod shadow;
+pub mod texture;
+pub mod upload;
 pub mod vulkan;
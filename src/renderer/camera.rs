@@ -0,0 +1,106 @@
+use cgmath::{perspective, ortho, Deg, Matrix4, Quaternion, Rad, Vector3};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+  Perspective { fov_y: Rad<f32> },
+  Orthographic { height: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+  pub position: [f32; 3],
+  pub orientation: [f32; 4],
+  pub projection: Projection,
+  pub near: f32,
+  pub far: f32,
+  pub aspect: f32,
+  // Normalized [x, y, width, height] of the render target this camera draws into.
+  pub viewport: [f32; 4],
+}
+
+impl Camera {
+  pub fn perspective(fov_y_degrees: f32, near: f32, far: f32) -> Self {
+    Self::new(
+      Projection::Perspective {
+        fov_y: Deg(fov_y_degrees).into(),
+      },
+      near,
+      far,
+    )
+  }
+
+  pub fn orthographic(height: f32, near: f32, far: f32) -> Self {
+    Self::new(Projection::Orthographic { height }, near, far)
+  }
+
+  fn new(projection: Projection, near: f32, far: f32) -> Self {
+    Self {
+      position: [0.0, 0.0, 0.0],
+      orientation: [0.0, 0.0, 0.0, 1.0],
+      projection,
+      near,
+      far,
+      aspect: 1.0,
+      viewport: [0.0, 0.0, 1.0, 1.0],
+    }
+  }
+
+  pub fn with_position(mut self, position: [f32; 3]) -> Self {
+    self.position = position;
+    self
+  }
+
+  pub fn with_orientation(mut self, orientation: [f32; 4]) -> Self {
+    self.orientation = orientation;
+    self
+  }
+
+  pub fn with_viewport(mut self, viewport: [f32; 4]) -> Self {
+    self.viewport = viewport;
+    self
+  }
+
+  pub fn set_aspect(&mut self, aspect: f32) {
+    if aspect.is_finite() && aspect > 0.0 {
+      self.aspect = aspect;
+    }
+  }
+
+  pub fn view_matrix(&self) -> Matrix4<f32> {
+    let [x, y, z, w] = self.orientation;
+    let [px, py, pz] = self.position;
+
+    Matrix4::from(Quaternion::new(w, x, y, z).conjugate())
+      * Matrix4::from_translation(Vector3::new(-px, -py, -pz))
+  }
+
+  // cgmath builds OpenGL projections; Vulkan clip space has y pointing down
+  // and depth in 0..1.
+  pub fn projection_matrix(&self) -> Matrix4<f32> {
+    let projection = match self.projection {
+      Projection::Perspective { fov_y } => perspective(fov_y, self.aspect, self.near, self.far),
+      Projection::Orthographic { height } => {
+        let half_height = height / 2.0;
+        let half_width = half_height * self.aspect;
+        ortho(
+          -half_width,
+          half_width,
+          -half_height,
+          half_height,
+          self.near,
+          self.far,
+        )
+      }
+    };
+
+    #[rustfmt::skip]
+    let correction = Matrix4::new(
+      1.0, 0.0, 0.0, 0.0,
+      0.0, -1.0, 0.0, 0.0,
+      0.0, 0.0, 0.5, 0.0,
+      0.0, 0.0, 0.5, 1.0,
+    );
+
+    correction * projection
+  }
+}
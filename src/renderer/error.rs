@@ -0,0 +1,119 @@
+use std::{error::Error, fmt};
+
+use image::ImageError;
+
+#[cfg(feature = "shader-reload")]
+use super::shader::ShaderError;
+use super::{graph::GraphError, obj::ObjError};
+
+#[derive(Debug)]
+pub enum RendererError {
+  UnknownActor(String),
+  DuplicateActor(String),
+  EmptyMesh(String),
+  UnknownTexture(String),
+  DuplicateTexture(String),
+  UnknownMaterial(String),
+  UnknownShader(String),
+  Image(ImageError),
+  Obj(ObjError),
+  #[cfg(feature = "shader-reload")]
+  Shader(ShaderError),
+  Graph(GraphError),
+  Gltf(gltf::Error),
+  Import(String),
+  Vulkan(Box<dyn Error>),
+}
+
+impl fmt::Display for RendererError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RendererError::UnknownActor(name) => write!(f, "no actor named {:?}", name),
+      RendererError::DuplicateActor(name) => write!(f, "an actor named {:?} already exists", name),
+      RendererError::EmptyMesh(name) => write!(f, "can't upload an empty mesh to {:?}", name),
+      RendererError::UnknownTexture(name) => write!(f, "no texture named {:?}", name),
+      RendererError::DuplicateTexture(name) => {
+        write!(f, "a texture named {:?} already exists", name)
+      }
+      RendererError::UnknownMaterial(name) => write!(f, "no material named {:?}", name),
+      RendererError::UnknownShader(name) => write!(f, "no shader named {:?}", name),
+      RendererError::Image(e) => write!(f, "image error: {}", e),
+      RendererError::Obj(e) => write!(f, "obj error: {}", e),
+      #[cfg(feature = "shader-reload")]
+      RendererError::Shader(e) => write!(f, "shader error: {}", e),
+      RendererError::Graph(e) => write!(f, "render graph error: {}", e),
+      RendererError::Gltf(e) => write!(f, "glTF error: {}", e),
+      RendererError::Import(message) => write!(f, "import error: {}", message),
+      RendererError::Vulkan(e) => write!(f, "vulkan error: {}", e),
+    }
+  }
+}
+
+impl Error for RendererError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      RendererError::Image(e) => Some(e),
+      RendererError::Obj(e) => Some(e),
+      #[cfg(feature = "shader-reload")]
+      RendererError::Shader(e) => Some(e),
+      RendererError::Graph(e) => Some(e),
+      RendererError::Gltf(e) => Some(e),
+      RendererError::Vulkan(e) => Some(e.as_ref()),
+      _ => None,
+    }
+  }
+}
+
+impl From<Box<dyn Error>> for RendererError {
+  fn from(e: Box<dyn Error>) -> Self {
+    RendererError::Vulkan(e)
+  }
+}
+
+impl From<ImageError> for RendererError {
+  fn from(e: ImageError) -> Self {
+    RendererError::Image(e)
+  }
+}
+
+impl From<ObjError> for RendererError {
+  fn from(e: ObjError) -> Self {
+    RendererError::Obj(e)
+  }
+}
+
+#[cfg(feature = "shader-reload")]
+impl From<ShaderError> for RendererError {
+  fn from(e: ShaderError) -> Self {
+    RendererError::Shader(e)
+  }
+}
+
+impl From<GraphError> for RendererError {
+  fn from(e: GraphError) -> Self {
+    RendererError::Graph(e)
+  }
+}
+
+impl From<gltf::Error> for RendererError {
+  fn from(e: gltf::Error) -> Self {
+    RendererError::Gltf(e)
+  }
+}
+
+#[cfg(test)]
+mod error_tests {
+  use super::*;
+
+  #[test]
+  fn test_display() {
+    assert_eq!(
+      RendererError::UnknownActor(String::from("player")).to_string(),
+      "no actor named \"player\""
+    );
+
+    let e = RendererError::from(Box::<dyn Error>::from("device lost"));
+    assert_eq!(e.to_string(), "vulkan error: device lost");
+    assert!(e.source().is_some());
+  }
+}
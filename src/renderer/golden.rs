@@ -0,0 +1,251 @@
+use std::path::PathBuf;
+
+use image::{Rgba, RgbaImage};
+use vulkano::VulkanLibrary;
+
+use super::{error::RendererError, vulkan::VulkanBackend};
+
+pub const GOLDEN_WIDTH: u32 = 64;
+pub const GOLDEN_HEIGHT: u32 = 64;
+
+#[derive(Clone, Copy)]
+pub struct Tolerance {
+  pub channel: u8,
+  pub mismatched_ratio: f32,
+}
+
+impl Default for Tolerance {
+  fn default() -> Self {
+    Self {
+      channel: 2,
+      mismatched_ratio: 0.005,
+    }
+  }
+}
+
+pub struct Comparison {
+  pub mismatched: usize,
+  pub diff: RgbaImage,
+}
+
+fn golden_dir() -> PathBuf {
+  PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    .join("tests")
+    .join("golden")
+}
+
+fn output_dir() -> PathBuf {
+  PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    .join("target")
+    .join("golden")
+}
+
+// Setting ISLE_SKIP_VULKAN_TESTS=1 makes this return None, and so does a host
+// without a Vulkan loader. A loader without a usable device fails the test.
+pub fn headless_backend() -> Option<VulkanBackend> {
+  if std::env::var("ISLE_SKIP_VULKAN_TESTS").is_ok() {
+    eprintln!("ISLE_SKIP_VULKAN_TESTS is set, skipping Vulkan test");
+    return None;
+  }
+
+  if let Err(e) = VulkanLibrary::new() {
+    eprintln!("Vulkan isn't available ({}), skipping Vulkan test", e);
+    return None;
+  }
+
+  match VulkanBackend::new_headless(GOLDEN_WIDTH, GOLDEN_HEIGHT) {
+    Ok(renderer) => Some(renderer),
+    Err(e) => panic!(
+      "Failed to create headless Vulkan backend: {}. Run with ISLE_SKIP_VULKAN_TESTS=1 to skip Vulkan tests.",
+      e
+    ),
+  }
+}
+
+pub fn render_headless(
+  setup: impl FnOnce(&mut VulkanBackend) -> Result<(), RendererError>,
+) -> Option<RgbaImage> {
+  let mut renderer = headless_backend()?;
+  setup(&mut renderer).expect("Failed to set up scene");
+  renderer.render();
+
+  Some(renderer.capture_frame().expect("Failed to capture frame"))
+}
+
+pub fn compare(expected: &RgbaImage, actual: &RgbaImage, tolerance: u8) -> Comparison {
+  let mut diff = RgbaImage::new(expected.width(), expected.height());
+  let mut mismatched = 0;
+
+  for (x, y, expected) in expected.enumerate_pixels() {
+    let actual = actual.get_pixel(x, y);
+    let matches = expected
+      .0
+      .iter()
+      .zip(actual.0.iter())
+      .all(|(e, a)| e.abs_diff(*a) <= tolerance);
+
+    let pixel = match matches {
+      true => {
+        let luma = (expected[0] as u16 + expected[1] as u16 + expected[2] as u16) / 12;
+        Rgba([luma as u8, luma as u8, luma as u8, 255])
+      }
+      false => {
+        mismatched += 1;
+        Rgba([255, 0, 0, 255])
+      }
+    };
+    diff.put_pixel(x, y, pixel);
+  }
+
+  Comparison { mismatched, diff }
+}
+
+// Set ISLE_UPDATE_GOLDEN=1 to overwrite the reference with the rendered frame.
+pub fn assert_golden(name: &str, actual: &RgbaImage, tolerance: Tolerance) {
+  let reference = golden_dir().join(format!("{}.png", name));
+
+  if std::env::var("ISLE_UPDATE_GOLDEN").is_ok() {
+    actual
+      .save(&reference)
+      .expect("Failed to write golden image");
+    return;
+  }
+
+  let expected = match image::open(&reference) {
+    Ok(expected) => expected.to_rgba8(),
+    Err(e) => panic!(
+      "Failed to open golden image {:?}: {}. Run with ISLE_UPDATE_GOLDEN=1 to create it.",
+      reference, e
+    ),
+  };
+
+  let output = output_dir();
+  let actual_path = output.join(format!("{}.actual.png", name));
+  let diff_path = output.join(format!("{}.diff.png", name));
+
+  if expected.dimensions() != actual.dimensions() {
+    std::fs::create_dir_all(&output).unwrap();
+    actual.save(&actual_path).unwrap();
+    panic!(
+      "{}: expected a {:?} frame, rendered {:?}; wrote {:?}",
+      name,
+      expected.dimensions(),
+      actual.dimensions(),
+      actual_path
+    );
+  }
+
+  let comparison = compare(&expected, actual, tolerance.channel);
+  let allowed = (expected.len() / 4) as f32 * tolerance.mismatched_ratio;
+
+  if comparison.mismatched as f32 > allowed {
+    std::fs::create_dir_all(&output).unwrap();
+    actual.save(&actual_path).unwrap();
+    comparison.diff.save(&diff_path).unwrap();
+    panic!(
+      "{}: {} pixels differ from {:?} (allowed {}); wrote {:?} and {:?}",
+      name, comparison.mismatched, reference, allowed as usize, actual_path, diff_path
+    );
+  }
+}
+
+#[cfg(test)]
+mod golden_tests {
+  use cgmath::{Deg, Matrix4, Quaternion, Rotation3, Vector3};
+
+  use super::*;
+  use crate::{
+    ecs::components::Light,
+    renderer::{
+      camera::Camera,
+      light::LightData,
+      mesh::Mesh,
+      post::{BloomOptions, PostOptions, Tonemap},
+    },
+  };
+
+  #[test]
+  fn test_compare() {
+    let expected = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(1, 2, Rgba([102, 100, 98, 255]));
+    actual.put_pixel(3, 3, Rgba([0, 0, 0, 255]));
+
+    let comparison = compare(&expected, &actual, 2);
+    assert_eq!(comparison.mismatched, 1);
+    assert_eq!(*comparison.diff.get_pixel(3, 3), Rgba([255, 0, 0, 255]));
+    assert_ne!(*comparison.diff.get_pixel(1, 2), Rgba([255, 0, 0, 255]));
+  }
+
+  fn get_luma(frame: &RgbaImage, x: u32, y: u32) -> u32 {
+    let [r, g, b, _] = frame.get_pixel(x, y).0;
+    r as u32 + g as u32 + b as u32
+  }
+
+  #[test]
+  fn test_triangle_golden() {
+    if let Some(frame) = render_headless(crate::load_demo_scene) {
+      assert_golden("triangle", &frame, Tolerance::default());
+    }
+  }
+
+  // A unit cube on a plane, seen from straight above, with a directional light
+  // tilted 30 degrees so the shadow falls towards +x.
+  fn load_shadow_scene(renderer: &mut VulkanBackend) -> Result<(), RendererError> {
+    let plane = renderer.create_actor(Some(String::from("plane")))?;
+    renderer.upload_mesh(plane, Mesh::plane(4.0, 0))?;
+    let cube = renderer.create_actor(Some(String::from("cube")))?;
+    renderer.upload_mesh(cube.clone(), Mesh::cube(1.0))?;
+    renderer.set_actor_translation(cube, [0.0, 0.5, 0.0])?;
+
+    let down = Quaternion::from_angle_x(Deg(-90.0));
+    renderer.set_camera(
+      Camera::perspective(60.0, 0.1, 10.0)
+        .with_position([0.0, 4.0, 0.0])
+        .with_orientation([down.v.x, down.v.y, down.v.z, down.s]),
+    );
+
+    let light = Light::directional([1.0; 3], 1.0)
+      .with_range(10.0)
+      .with_shadows();
+    let world = Matrix4::from_translation(Vector3::new(0.0, 5.0, 0.0))
+      * Matrix4::from(Quaternion::from_angle_z(Deg(30.0)) * down);
+    renderer.set_lights(vec![LightData::new(&light, world)]);
+    renderer.set_ambient_light([0.1; 3]);
+    Ok(())
+  }
+
+  #[test]
+  fn test_shadow_golden() {
+    let Some(frame) = render_headless(load_shadow_scene) else {
+      return;
+    };
+
+    // The plane right of the cube is in its shadow, the plane left of it isn't.
+    let row = GOLDEN_HEIGHT / 2;
+    assert!(get_luma(&frame, 43, row) < get_luma(&frame, 21, row));
+    assert_golden("shadows", &frame, Tolerance::default());
+  }
+
+  #[test]
+  fn test_post_golden() {
+    let Some(frame) = render_headless(|renderer| {
+      crate::load_demo_scene(renderer)?;
+      renderer.set_post_options(PostOptions {
+        exposure: 2.0,
+        tonemap: Tonemap::Aces,
+        bloom: Some(BloomOptions::default()),
+        fxaa: true,
+        vignette: 0.5,
+        ..Default::default()
+      })
+    }) else {
+      return;
+    };
+
+    // Both only show the clear color, but the vignette darkens the corner more
+    // than the middle of the edge.
+    assert!(get_luma(&frame, 0, 0) < get_luma(&frame, 0, GOLDEN_HEIGHT / 2));
+    assert_golden("post", &frame, Tolerance::default());
+  }
+}
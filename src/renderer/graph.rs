@@ -0,0 +1,764 @@
+use std::{
+  collections::{BTreeSet, HashMap, HashSet},
+  error::Error,
+  fmt,
+  sync::Arc,
+};
+
+use vulkano::{
+  command_buffer::{
+    AutoCommandBufferBuilder, PrimaryAutoCommandBuffer, RenderPassBeginInfo, SubpassContents,
+  },
+  device::Device,
+  format::{ClearValue, Format},
+  image::{
+    view::{ImageView, ImageViewAbstract},
+    AttachmentImage, ImageLayout, ImageUsage, SampleCount,
+  },
+  pipeline::graphics::viewport::Viewport,
+  render_pass::{
+    AttachmentDescription, AttachmentReference, Framebuffer, FramebufferCreateInfo, LoadOp,
+    RenderPass, RenderPassCreateInfo, StoreOp, Subpass, SubpassDescription,
+  },
+};
+
+// The image the frame ends up in: a swapchain image or the offscreen target.
+// It's handed to `execute` every frame rather than allocated by the graph.
+pub const TARGET: &str = "target";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttachmentSize {
+  // A fraction of the target's extent, reallocated when the target resizes.
+  Relative(f32),
+  Absolute([u32; 2]),
+}
+
+#[derive(Clone, Debug)]
+pub struct AttachmentDesc {
+  pub format: Format,
+  pub size: AttachmentSize,
+  // Applied by the first pass writing the attachment. Without one, the target
+  // keeps its contents and other attachments start undefined.
+  pub clear: Option<ClearValue>,
+}
+
+impl AttachmentDesc {
+  pub fn new(format: Format) -> Self {
+    Self {
+      format,
+      size: AttachmentSize::Relative(1.0),
+      clear: None,
+    }
+  }
+
+  pub fn with_size(mut self, size: AttachmentSize) -> Self {
+    self.size = size;
+    self
+  }
+
+  pub fn with_clear(mut self, clear: impl Into<ClearValue>) -> Self {
+    self.clear = Some(clear.into());
+    self
+  }
+}
+
+// A pass draws into its color and depth attachments, and samples the ones it
+// reads, which must have been written by earlier passes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PassDesc {
+  pub name: String,
+  pub reads: Vec<String>,
+  pub colors: Vec<String>,
+  pub depth: Option<String>,
+}
+
+impl PassDesc {
+  pub fn new(name: impl Into<String>) -> Self {
+    Self {
+      name: name.into(),
+      ..Default::default()
+    }
+  }
+
+  pub fn read(mut self, attachment: impl Into<String>) -> Self {
+    self.reads.push(attachment.into());
+    self
+  }
+
+  pub fn color(mut self, attachment: impl Into<String>) -> Self {
+    self.colors.push(attachment.into());
+    self
+  }
+
+  pub fn depth(mut self, attachment: impl Into<String>) -> Self {
+    self.depth = Some(attachment.into());
+    self
+  }
+
+  // Color attachments first, then depth, in render pass attachment order.
+  fn outputs(&self) -> impl Iterator<Item = &String> {
+    self.colors.iter().chain(&self.depth)
+  }
+}
+
+// What a pass gets while it's recorded, inside its render pass.
+pub struct PassContext {
+  pub name: String,
+  pub subpass: Subpass,
+  pub viewport: Viewport,
+  pub inputs: HashMap<String, Arc<ImageView<AttachmentImage>>>,
+}
+
+pub type RecordPass = Box<
+  dyn Fn(
+    &PassContext,
+    &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+  ) -> Result<(), Box<dyn Error>>,
+>;
+
+#[derive(Debug)]
+pub enum GraphError {
+  DuplicateAttachment(String),
+  DuplicatePass(String),
+  UnknownPass(String),
+  NoAttachment(String),
+  UnknownAttachment { pass: String, attachment: String },
+  Unwritten { pass: String, attachment: String },
+  ReadAndWrite { pass: String, attachment: String },
+  Cycle(Vec<String>),
+  Vulkan(Box<dyn Error>),
+}
+
+impl fmt::Display for GraphError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      GraphError::DuplicateAttachment(attachment) => {
+        write!(f, "an attachment named {:?} already exists", attachment)
+      }
+      GraphError::DuplicatePass(pass) => write!(f, "a pass named {:?} already exists", pass),
+      GraphError::UnknownPass(pass) => write!(f, "no pass named {:?}", pass),
+      GraphError::NoAttachment(attachment) => write!(f, "no attachment named {:?}", attachment),
+      GraphError::UnknownAttachment { pass, attachment } => {
+        write!(
+          f,
+          "pass {:?} uses unknown attachment {:?}",
+          pass, attachment
+        )
+      }
+      GraphError::Unwritten { pass, attachment } => write!(
+        f,
+        "pass {:?} reads {:?}, which no pass writes",
+        pass, attachment
+      ),
+      GraphError::ReadAndWrite { pass, attachment } => {
+        write!(f, "pass {:?} both reads and writes {:?}", pass, attachment)
+      }
+      GraphError::Cycle(passes) => write!(f, "passes {:?} depend on each other", passes),
+      GraphError::Vulkan(e) => write!(f, "vulkan error: {}", e),
+    }
+  }
+}
+
+impl Error for GraphError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      GraphError::Vulkan(e) => Some(e.as_ref()),
+      _ => None,
+    }
+  }
+}
+
+struct Pass {
+  desc: PassDesc,
+  record: Option<RecordPass>,
+  enabled: bool,
+  render_pass: Option<Arc<RenderPass>>,
+  clear_values: Vec<Option<ClearValue>>,
+  // Passes drawing into the target get theirs per target image instead.
+  framebuffer: Option<Arc<Framebuffer>>,
+}
+
+// Passes are declared up front and ordered by the attachments they share, so
+// the declaration order only matters between passes writing the same
+// attachment. Barriers and layout transitions between passes are left to
+// vulkano's command buffer builder.
+pub struct RenderGraph {
+  device: Arc<Device>,
+  extent: [u32; 2],
+  attachments: HashMap<String, AttachmentDesc>,
+  passes: Vec<Pass>,
+  order: Option<Vec<usize>>,
+  images: HashMap<String, Arc<ImageView<AttachmentImage>>>,
+  // Attachments allocated since any pass last wrote them.
+  unwritten: HashSet<String>,
+  // Keyed by pass and target view.
+  target_framebuffers: HashMap<(usize, usize), Arc<Framebuffer>>,
+}
+
+impl RenderGraph {
+  pub fn new(device: Arc<Device>, extent: [u32; 2]) -> Self {
+    Self {
+      device,
+      extent,
+      attachments: HashMap::new(),
+      passes: Vec::new(),
+      order: None,
+      images: HashMap::new(),
+      unwritten: HashSet::new(),
+      target_framebuffers: HashMap::new(),
+    }
+  }
+
+  // Adding or removing anything means the graph has to be compiled again.
+  pub fn add_attachment(
+    &mut self,
+    name: impl Into<String>,
+    desc: AttachmentDesc,
+  ) -> Result<(), GraphError> {
+    let name = name.into();
+    if self.attachments.contains_key(&name) {
+      return Err(GraphError::DuplicateAttachment(name));
+    }
+
+    self.attachments.insert(name, desc);
+    self.order = None;
+    Ok(())
+  }
+
+  // Only reallocates the attachment, so the graph stays compiled.
+  pub fn set_attachment_size(
+    &mut self,
+    name: &str,
+    size: AttachmentSize,
+  ) -> Result<(), GraphError> {
+    let Some(attachment) = self.attachments.get_mut(name) else {
+      return Err(GraphError::NoAttachment(String::from(name)));
+    };
+
+    attachment.size = size;
+    match self.order {
+      Some(_) => self.allocate().map_err(GraphError::Vulkan),
+      None => Ok(()),
+    }
+  }
+
+  // Passes without a recorder are recorded by the callback given to `execute`.
+  pub fn add_pass(&mut self, desc: PassDesc, record: Option<RecordPass>) -> Result<(), GraphError> {
+    self.insert_pass(self.passes.len(), desc, record)
+  }
+
+  // Declares a pass at `index` among the others, for passes that have to keep
+  // their place ahead of ones declared after them.
+  pub fn insert_pass(
+    &mut self,
+    index: usize,
+    desc: PassDesc,
+    record: Option<RecordPass>,
+  ) -> Result<(), GraphError> {
+    if self.passes.iter().any(|pass| pass.desc.name == desc.name) {
+      return Err(GraphError::DuplicatePass(desc.name));
+    }
+
+    self.passes.insert(
+      index.min(self.passes.len()),
+      Pass {
+        desc,
+        record,
+        enabled: true,
+        render_pass: None,
+        clear_values: Vec::new(),
+        framebuffer: None,
+      },
+    );
+    self.order = None;
+    Ok(())
+  }
+
+  // Where a pass was declared, for putting something back in its place.
+  pub fn pass_index(&self, name: &str) -> Option<usize> {
+    self.passes.iter().position(|pass| pass.desc.name == name)
+  }
+
+  pub fn remove_pass(&mut self, name: &str) -> Result<(), GraphError> {
+    let Some(index) = self.pass_index(name) else {
+      return Err(GraphError::UnknownPass(String::from(name)));
+    };
+
+    self.passes.remove(index);
+    self.order = None;
+    Ok(())
+  }
+
+  pub fn compile(&mut self) -> Result<(), GraphError> {
+    let descs: Vec<&PassDesc> = self.passes.iter().map(|pass| &pass.desc).collect();
+    let order = get_pass_order(&descs, &self.attachments)?;
+
+    for (position, &index) in order.iter().enumerate() {
+      let desc = &self.passes[index].desc;
+      let earlier = &order[..position];
+      let later = &order[position + 1..];
+
+      let mut attachments = Vec::new();
+      let mut clear_values = Vec::new();
+      for name in desc.outputs() {
+        let attachment = &self.attachments[name];
+        let first = !earlier.iter().any(|&other| {
+          self.passes[other]
+            .desc
+            .outputs()
+            .any(|output| output == name)
+        });
+        let needed = name == TARGET
+          || later.iter().any(|&other| {
+            let other = &self.passes[other].desc;
+            other.reads.contains(name) || other.outputs().any(|output| output == name)
+          });
+
+        let load_op = match (first, attachment.clear) {
+          (true, Some(_)) => LoadOp::Clear,
+          (true, None) if name != TARGET => LoadOp::DontCare,
+          _ => LoadOp::Load,
+        };
+        clear_values.push(match load_op {
+          LoadOp::Clear => attachment.clear,
+          _ => None,
+        });
+
+        let layout = get_attachment_layout(attachment.format);
+        attachments.push(AttachmentDescription {
+          format: Some(attachment.format),
+          samples: SampleCount::Sample1,
+          load_op,
+          store_op: match needed {
+            true => StoreOp::Store,
+            false => StoreOp::DontCare,
+          },
+          initial_layout: layout,
+          final_layout: layout,
+          ..Default::default()
+        });
+      }
+
+      let reference = |index: usize| AttachmentReference {
+        attachment: index as u32,
+        layout: attachments[index].initial_layout,
+        ..Default::default()
+      };
+      let subpass = SubpassDescription {
+        color_attachments: (0..desc.colors.len())
+          .map(|index| Some(reference(index)))
+          .collect(),
+        depth_stencil_attachment: desc.depth.as_ref().map(|_| reference(desc.colors.len())),
+        ..Default::default()
+      };
+
+      let render_pass = RenderPass::new(
+        self.device.clone(),
+        RenderPassCreateInfo {
+          attachments,
+          subpasses: vec![subpass],
+          ..Default::default()
+        },
+      )
+      .map_err(|e| GraphError::Vulkan(e.into()))?;
+
+      let pass = &mut self.passes[index];
+      pass.render_pass = Some(render_pass);
+      pass.clear_values = clear_values;
+    }
+
+    self.order = Some(order);
+    self.allocate().map_err(GraphError::Vulkan)
+  }
+
+  // Reallocates attachments sized relative to the target.
+  pub fn resize(&mut self, extent: [u32; 2]) -> Result<(), GraphError> {
+    self.extent = extent;
+
+    match self.order {
+      Some(_) => self.allocate().map_err(GraphError::Vulkan),
+      None => Ok(()),
+    }
+  }
+
+  // Disabled passes are skipped by `execute` without recompiling the graph.
+  // They still run once whenever their attachments are reallocated, so passes
+  // sampling those attachments never see uninitialized images.
+  pub fn set_pass_enabled(&mut self, name: &str, enabled: bool) -> Result<(), GraphError> {
+    let Some(pass) = self.passes.iter_mut().find(|pass| pass.desc.name == name) else {
+      return Err(GraphError::UnknownPass(String::from(name)));
+    };
+
+    pass.enabled = enabled;
+    Ok(())
+  }
+
+  pub fn subpass(&self, pass: &str) -> Option<Subpass> {
+    let pass = self.passes.iter().find(|other| other.desc.name == pass)?;
+    Subpass::from(pass.render_pass.clone()?, 0)
+  }
+
+  pub fn attachment(&self, name: &str) -> Option<&Arc<ImageView<AttachmentImage>>> {
+    self.images.get(name)
+  }
+
+  #[cfg(test)]
+  pub(super) fn pass_order(&self) -> Option<Vec<&str>> {
+    let order = self.order.as_ref()?;
+    Some(
+      order
+        .iter()
+        .map(|&index| self.passes[index].desc.name.as_str())
+        .collect(),
+    )
+  }
+
+  pub fn execute(
+    &mut self,
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    target: Arc<dyn ImageViewAbstract>,
+    mut record: impl FnMut(
+      &PassContext,
+      &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    ) -> Result<(), Box<dyn Error>>,
+  ) -> Result<(), Box<dyn Error>> {
+    let Some(order) = self.order.clone() else {
+      return Err("the render graph has to be compiled first".into());
+    };
+
+    for index in order {
+      let pass = &self.passes[index];
+      let outputs: Vec<String> = pass.desc.outputs().cloned().collect();
+      if !pass.enabled && !outputs.iter().any(|name| self.unwritten.contains(name)) {
+        continue;
+      }
+
+      let framebuffer = self.get_framebuffer(index, &target)?;
+      let pass = &self.passes[index];
+      let Some(subpass) = self.subpass(&pass.desc.name) else {
+        return Err(format!("pass {:?} has no render pass", pass.desc.name).into());
+      };
+
+      let [width, height] = framebuffer.extent();
+      let context = PassContext {
+        name: pass.desc.name.clone(),
+        subpass,
+        viewport: Viewport {
+          origin: [0.0, 0.0],
+          dimensions: [width as f32, height as f32],
+          depth_range: 0.0..1.0,
+        },
+        inputs: pass
+          .desc
+          .reads
+          .iter()
+          .filter_map(|name| Some((name.clone(), self.images.get(name)?.clone())))
+          .collect(),
+      };
+
+      builder.begin_render_pass(
+        RenderPassBeginInfo {
+          clear_values: pass.clear_values.clone(),
+          ..RenderPassBeginInfo::framebuffer(framebuffer)
+        },
+        SubpassContents::Inline,
+      )?;
+
+      match &pass.record {
+        Some(own) => own(&context, builder)?,
+        None => record(&context, builder)?,
+      }
+
+      builder.end_render_pass()?;
+
+      for name in &outputs {
+        self.unwritten.remove(name);
+      }
+    }
+
+    Ok(())
+  }
+
+  // Framebuffers of passes drawing into the target are dropped along with the
+  // rest, since they belong to render passes and pass indices that a compile
+  // may have replaced, and the target images usually change with the extent.
+  fn allocate(&mut self) -> Result<(), Box<dyn Error>> {
+    self.target_framebuffers.clear();
+
+    let mut images = HashMap::new();
+    for (name, attachment) in &self.attachments {
+      if name == TARGET {
+        continue;
+      }
+
+      let sampled = self
+        .passes
+        .iter()
+        .any(|pass| pass.desc.reads.contains(name));
+      let writers = self
+        .passes
+        .iter()
+        .filter(|pass| pass.desc.outputs().any(|output| output == name))
+        .count();
+      if writers == 0 {
+        continue;
+      }
+
+      let depth = attachment.format.aspects().depth;
+      let image = AttachmentImage::with_usage(
+        self.device.clone(),
+        get_attachment_extent(attachment.size, self.extent),
+        attachment.format,
+        ImageUsage {
+          color_attachment: !depth,
+          depth_stencil_attachment: depth,
+          sampled,
+          // Attachments that live within one pass never need memory behind
+          // them on tilers.
+          transient_attachment: !sampled && writers == 1,
+          ..Default::default()
+        },
+      )?;
+      images.insert(name.clone(), ImageView::new_default(image)?);
+    }
+    self.unwritten = images.keys().cloned().collect();
+    self.images = images;
+
+    for index in 0..self.passes.len() {
+      let uses_target = self.passes[index]
+        .desc
+        .outputs()
+        .any(|output| output == TARGET);
+      self.passes[index].framebuffer = match uses_target {
+        true => None,
+        false => Some(self.create_framebuffer(index, None)?),
+      };
+    }
+
+    Ok(())
+  }
+
+  fn get_framebuffer(
+    &mut self,
+    index: usize,
+    target: &Arc<dyn ImageViewAbstract>,
+  ) -> Result<Arc<Framebuffer>, Box<dyn Error>> {
+    if let Some(framebuffer) = &self.passes[index].framebuffer {
+      return Ok(framebuffer.clone());
+    }
+
+    let key = (index, Arc::as_ptr(target) as *const () as usize);
+    if let Some(framebuffer) = self.target_framebuffers.get(&key) {
+      return Ok(framebuffer.clone());
+    }
+
+    let framebuffer = self.create_framebuffer(index, Some(target))?;
+    self.target_framebuffers.insert(key, framebuffer.clone());
+    Ok(framebuffer)
+  }
+
+  fn create_framebuffer(
+    &self,
+    index: usize,
+    target: Option<&Arc<dyn ImageViewAbstract>>,
+  ) -> Result<Arc<Framebuffer>, Box<dyn Error>> {
+    let pass = &self.passes[index];
+    let Some(render_pass) = pass.render_pass.clone() else {
+      return Err(format!("pass {:?} has no render pass", pass.desc.name).into());
+    };
+
+    let mut attachments = Vec::new();
+    for name in pass.desc.outputs() {
+      let view: Arc<dyn ImageViewAbstract> = match (name == TARGET, target) {
+        (true, Some(target)) => target.clone(),
+        (true, None) => return Err("no target to draw into".into()),
+        (false, _) => match self.images.get(name) {
+          Some(image) => image.clone(),
+          None => return Err(format!("attachment {:?} isn't allocated", name).into()),
+        },
+      };
+      attachments.push(view);
+    }
+
+    Ok(Framebuffer::new(
+      render_pass,
+      FramebufferCreateInfo {
+        attachments,
+        ..Default::default()
+      },
+    )?)
+  }
+}
+
+// Orders passes so every attachment is written before it's read. Passes writing
+// the same attachment keep their declaration order, and otherwise independent
+// passes do too.
+fn get_pass_order(
+  passes: &[&PassDesc],
+  attachments: &HashMap<String, AttachmentDesc>,
+) -> Result<Vec<usize>, GraphError> {
+  let mut writers: HashMap<&String, Vec<usize>> = HashMap::new();
+  for (index, pass) in passes.iter().enumerate() {
+    for name in pass.reads.iter().chain(pass.outputs()) {
+      if !attachments.contains_key(name) {
+        return Err(GraphError::UnknownAttachment {
+          pass: pass.name.clone(),
+          attachment: name.clone(),
+        });
+      }
+    }
+
+    for name in pass.outputs() {
+      if pass.reads.contains(name) {
+        return Err(GraphError::ReadAndWrite {
+          pass: pass.name.clone(),
+          attachment: name.clone(),
+        });
+      }
+      writers.entry(name).or_default().push(index);
+    }
+  }
+
+  let mut dependents = vec![BTreeSet::new(); passes.len()];
+  for indices in writers.values() {
+    for pair in indices.windows(2) {
+      dependents[pair[0]].insert(pair[1]);
+    }
+  }
+  for (index, pass) in passes.iter().enumerate() {
+    for name in &pass.reads {
+      let Some(indices) = writers.get(name) else {
+        return Err(GraphError::Unwritten {
+          pass: pass.name.clone(),
+          attachment: name.clone(),
+        });
+      };
+
+      for &writer in indices {
+        dependents[writer].insert(index);
+      }
+    }
+  }
+
+  let mut dependencies = vec![0; passes.len()];
+  for &dependent in dependents.iter().flatten() {
+    dependencies[dependent] += 1;
+  }
+
+  let mut ready: BTreeSet<usize> = (0..passes.len())
+    .filter(|&index| dependencies[index] == 0)
+    .collect();
+  let mut order = Vec::new();
+  while let Some(index) = ready.pop_first() {
+    order.push(index);
+    for &dependent in &dependents[index] {
+      dependencies[dependent] -= 1;
+      if dependencies[dependent] == 0 {
+        ready.insert(dependent);
+      }
+    }
+  }
+
+  if order.len() < passes.len() {
+    return Err(GraphError::Cycle(
+      (0..passes.len())
+        .filter(|index| !order.contains(index))
+        .map(|index| passes[index].name.clone())
+        .collect(),
+    ));
+  }
+
+  Ok(order)
+}
+
+fn get_attachment_layout(format: Format) -> ImageLayout {
+  match format.aspects().depth || format.aspects().stencil {
+    true => ImageLayout::DepthStencilAttachmentOptimal,
+    false => ImageLayout::ColorAttachmentOptimal,
+  }
+}
+
+fn get_attachment_extent(size: AttachmentSize, [width, height]: [u32; 2]) -> [u32; 2] {
+  match size {
+    AttachmentSize::Relative(scale) => {
+      [width, height].map(|dimension| ((dimension as f32 * scale).round() as u32).max(1))
+    }
+    AttachmentSize::Absolute(extent) => extent.map(|dimension| dimension.max(1)),
+  }
+}
+
+#[cfg(test)]
+mod graph_tests {
+  use super::*;
+
+  fn get_attachments(names: &[&str]) -> HashMap<String, AttachmentDesc> {
+    names
+      .iter()
+      .map(|name| {
+        (
+          String::from(*name),
+          AttachmentDesc::new(Format::R8G8B8A8_UNORM),
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_pass_order() {
+    let attachments = get_attachments(&[TARGET, "hdr", "depth", "shadow_map"]);
+    let post = PassDesc::new("post").read("hdr").color(TARGET);
+    let scene = PassDesc::new("scene")
+      .read("shadow_map")
+      .color("hdr")
+      .depth("depth");
+    let shadow = PassDesc::new("shadow").depth("shadow_map");
+    let ui = PassDesc::new("ui").color(TARGET);
+
+    let order = get_pass_order(&[&post, &ui, &scene, &shadow], &attachments).unwrap();
+    assert_eq!(order, vec![3, 2, 0, 1]);
+  }
+
+  #[test]
+  fn test_invalid_graphs() {
+    let attachments = get_attachments(&["a", "b"]);
+
+    let first = PassDesc::new("first").read("a").color("b");
+    let second = PassDesc::new("second").read("b").color("a");
+    assert!(matches!(
+      get_pass_order(&[&first, &second], &attachments),
+      Err(GraphError::Cycle(passes)) if passes.len() == 2
+    ));
+
+    let unwritten = PassDesc::new("unwritten").read("a").color("b");
+    assert!(matches!(
+      get_pass_order(&[&unwritten], &attachments),
+      Err(GraphError::Unwritten { .. })
+    ));
+
+    let feedback = PassDesc::new("feedback").read("a").color("a");
+    assert!(matches!(
+      get_pass_order(&[&feedback], &attachments),
+      Err(GraphError::ReadAndWrite { .. })
+    ));
+
+    let unknown = PassDesc::new("unknown").color("c");
+    assert_eq!(
+      get_pass_order(&[&unknown], &attachments)
+        .unwrap_err()
+        .to_string(),
+      "pass \"unknown\" uses unknown attachment \"c\""
+    );
+  }
+
+  #[test]
+  fn test_attachment_extent() {
+    assert_eq!(
+      get_attachment_extent(AttachmentSize::Relative(0.5), [100, 1]),
+      [50, 1]
+    );
+    assert_eq!(
+      get_attachment_extent(AttachmentSize::Absolute([256, 0]), [100, 100]),
+      [256, 1]
+    );
+  }
+}
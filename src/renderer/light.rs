@@ -0,0 +1,190 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Vector3, Vector4};
+
+use super::camera::Camera;
+use crate::{
+  ecs::components::{get_world_matrix, Light, LightKind},
+  registry::entity_registry::EntityRegistry,
+};
+
+// Size of the light array in shaders/default.frag; lights past it are ignored.
+pub const MAX_LIGHTS: usize = 16;
+
+const DIRECTIONAL: f32 = 0.0;
+const POINT: f32 = 1.0;
+const SPOT: f32 = 2.0;
+
+// Near plane of spot light shadow maps.
+const SHADOW_NEAR: f32 = 0.05;
+
+// Laid out like the `Light` struct in shaders/default.frag; the shadow map is
+// picked while uploading.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightData {
+  // w holds the kind of light.
+  pub(super) position: [f32; 4],
+  // w holds the range.
+  pub(super) direction: [f32; 4],
+  // Scaled by the intensity.
+  pub(super) color: [f32; 4],
+  // Cosines of the inner and outer spot angles.
+  pub(super) cone: [f32; 4],
+  pub(super) shadow_matrix: Option<Matrix4<f32>>,
+}
+
+impl LightData {
+  pub fn new(light: &Light, world: Matrix4<f32>) -> Self {
+    let position = world * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    let direction = (world * Vector4::new(0.0, 0.0, -1.0, 0.0)).truncate();
+    let direction = match direction.magnitude2() > 0.0 {
+      true => direction.normalize(),
+      false => direction,
+    };
+
+    let (kind, cone) = match light.kind {
+      LightKind::Directional => (DIRECTIONAL, [-1.0, -1.0]),
+      LightKind::Point => (POINT, [-1.0, -1.0]),
+      LightKind::Spot {
+        inner_angle,
+        outer_angle,
+      } => (SPOT, [inner_angle.cos(), outer_angle.cos()]),
+    };
+
+    let [r, g, b] = light.color.map(|channel| channel * light.intensity);
+
+    Self {
+      position: [position.x, position.y, position.z, kind],
+      direction: [direction.x, direction.y, direction.z, light.range],
+      color: [r, g, b, 1.0],
+      cone: [cone[0], cone[1], 0.0, 0.0],
+      shadow_matrix: match light.casts_shadows {
+        true => get_shadow_matrix(light, position.truncate(), direction),
+        false => None,
+      },
+    }
+  }
+}
+
+// Directional shadows cover a box of `range` around the light's position,
+// spot shadows the light's cone up to its range.
+fn get_shadow_matrix(
+  light: &Light,
+  position: Vector3<f32>,
+  direction: Vector3<f32>,
+) -> Option<Matrix4<f32>> {
+  let projection = match light.kind {
+    LightKind::Directional => Camera::orthographic(light.range * 2.0, -light.range, light.range),
+    LightKind::Spot { outer_angle, .. } => Camera::perspective(
+      (outer_angle * 2.0).to_degrees().clamp(1.0, 170.0),
+      SHADOW_NEAR,
+      light.range,
+    ),
+    LightKind::Point => return None,
+  };
+
+  if direction.magnitude2() == 0.0 {
+    return None;
+  }
+
+  let up = match direction.y.abs() > 0.99 {
+    true => Vector3::unit_z(),
+    false => Vector3::unit_y(),
+  };
+  let view = Matrix4::look_to_rh(Point3::from_vec(position), direction, up);
+
+  Some(projection.projection_matrix() * view)
+}
+
+// Gathers every entity with a Light component, placed by its world transform.
+pub fn get_scene_lights(entities: &EntityRegistry) -> Vec<LightData> {
+  let Some(lit) = entities.get_entities_by_component::<Light>() else {
+    return Vec::new();
+  };
+
+  lit
+    .into_iter()
+    .filter_map(|entity| {
+      let light = entities.get_component::<Light>(entity)?;
+      Some(LightData::new(light, get_world_matrix(entities, entity)))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod light_tests {
+  use cgmath::{Deg, Quaternion, Rotation3, SquareMatrix, Vector3};
+
+  use super::*;
+  use crate::{ecs::components::Transform, renderer::golden::headless_backend};
+
+  #[test]
+  fn test_light_data() {
+    let light = Light::spot([1.0, 0.5, 0.0], 2.0, 10.0, 0.0, std::f32::consts::FRAC_PI_2);
+    let world = Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0))
+      * Matrix4::from(Quaternion::from_angle_y(Deg(90.0)));
+
+    let data = LightData::new(&light, world);
+    assert_eq!(data.position, [1.0, 2.0, 3.0, SPOT]);
+    assert!((data.direction[0] + 1.0).abs() < 1e-5 && data.direction[2].abs() < 1e-5);
+    assert_eq!(data.direction[3], 10.0);
+    assert_eq!(data.color, [2.0, 1.0, 0.0, 1.0]);
+    assert_eq!(data.cone[0], 1.0);
+    assert!(data.cone[1].abs() < 1e-5);
+
+    let data = LightData::new(&Light::point([1.0; 3], 1.0, 5.0), Matrix4::identity());
+    assert_eq!(data.position[3], POINT);
+    assert_eq!(data.direction, [0.0, 0.0, -1.0, 5.0]);
+  }
+
+  #[test]
+  fn test_shadow_matrix() {
+    let light = Light::directional([1.0; 3], 1.0).with_range(10.0);
+    assert_eq!(
+      LightData::new(&light, Matrix4::identity()).shadow_matrix,
+      None
+    );
+
+    // Looking straight down from 5 units up, the point below the light lands
+    // in the middle of the map, halfway through its depth.
+    let world = Matrix4::from_translation(Vector3::new(0.0, 5.0, 0.0))
+      * Matrix4::from(Quaternion::from_angle_x(Deg(-90.0)));
+    let data = LightData::new(&light.with_shadows(), world);
+    let clip = data.shadow_matrix.unwrap() * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    assert!(clip.x.abs() < 1e-5 && clip.y.abs() < 1e-5);
+    assert!((clip.z / clip.w - 0.75).abs() < 1e-5);
+
+    let point = Light::point([1.0; 3], 1.0, 5.0).with_shadows();
+    assert_eq!(
+      LightData::new(&point, Matrix4::identity()).shadow_matrix,
+      None
+    );
+  }
+
+  #[test]
+  fn test_scene_lights() {
+    let mut entities = EntityRegistry::new();
+    let parent = String::from("parent");
+    let lamp = String::from("lamp");
+
+    let light = Light::point([1.0, 0.5, 0.25], 2.0, 5.0);
+    entities.add_component(
+      parent.clone(),
+      Transform {
+        translation: [1.0, 2.0, 3.0],
+        ..Default::default()
+      },
+    );
+    entities.add_component(lamp.clone(), light);
+    entities.set_parent(lamp, Some(parent));
+
+    let lights = get_scene_lights(&entities);
+    assert_eq!(lights.len(), 1);
+    assert_eq!(lights[0].position, [1.0, 2.0, 3.0, POINT]);
+    assert_eq!(lights[0].color, [2.0, 1.0, 0.5, 1.0]);
+
+    let Some(mut renderer) = headless_backend() else {
+      return;
+    };
+    renderer.set_lights(get_scene_lights(&entities));
+    assert_eq!(renderer.lights(), lights.as_slice());
+  }
+}
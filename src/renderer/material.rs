@@ -0,0 +1,189 @@
+use std::sync::Arc;
+
+use vulkano::{
+  pipeline::{
+    graphics::{
+      color_blend::ColorBlendState,
+      depth_stencil::{CompareOp, DepthState, DepthStencilState},
+      rasterization::{self, FrontFace, RasterizationState},
+    },
+    StateMode,
+  },
+  shader::ShaderModule,
+};
+
+pub const DEFAULT_SHADER: &str = "default";
+pub const DEFAULT_MATERIAL: &str = "default";
+
+// Custom shaders have to keep the default interface: the vertex layout, the
+// camera and lights at set 0, the texture at set 1 and the model matrix push
+// constant.
+#[derive(Clone)]
+pub struct ShaderPair {
+  pub vertex: Arc<ShaderModule>,
+  pub fragment: Arc<ShaderModule>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BlendMode {
+  Opaque,
+  Alpha,
+  Additive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CullMode {
+  None,
+  Back,
+  Front,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DepthTest {
+  Disabled,
+  ReadOnly,
+  ReadWrite,
+}
+
+// Two materials with the same shader and state share a pipeline.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Material {
+  pub shader: String,
+  pub blend: BlendMode,
+  pub cull: CullMode,
+  pub depth: DepthTest,
+}
+
+impl Default for Material {
+  fn default() -> Self {
+    Self::new(DEFAULT_SHADER)
+  }
+}
+
+impl Material {
+  pub fn new(shader: impl Into<String>) -> Self {
+    Self {
+      shader: shader.into(),
+      blend: BlendMode::Opaque,
+      cull: CullMode::Back,
+      depth: DepthTest::ReadWrite,
+    }
+  }
+
+  // Blended materials don't write depth, so they don't hide what's drawn
+  // behind them afterwards.
+  pub fn transparent(shader: impl Into<String>) -> Self {
+    Self {
+      blend: BlendMode::Alpha,
+      depth: DepthTest::ReadOnly,
+      ..Self::new(shader)
+    }
+  }
+
+  pub fn with_blend(mut self, blend: BlendMode) -> Self {
+    self.blend = blend;
+    self
+  }
+
+  pub fn with_cull(mut self, cull: CullMode) -> Self {
+    self.cull = cull;
+    self
+  }
+
+  pub fn with_depth(mut self, depth: DepthTest) -> Self {
+    self.depth = depth;
+    self
+  }
+
+  pub fn is_transparent(&self) -> bool {
+    self.blend != BlendMode::Opaque
+  }
+
+  pub(super) fn color_blend_state(&self) -> ColorBlendState {
+    let state = ColorBlendState::new(1);
+    match self.blend {
+      BlendMode::Opaque => state,
+      BlendMode::Alpha => state.blend_alpha(),
+      BlendMode::Additive => state.blend_additive(),
+    }
+  }
+
+  // Models are wound counter-clockwise. The projection flips y to match Vulkan's
+  // y-down framebuffer, which keeps them counter-clockwise on screen, and that's
+  // the winding FrontFace is measured in.
+  pub(super) fn rasterization_state(&self) -> RasterizationState {
+    RasterizationState::new()
+      .front_face(FrontFace::CounterClockwise)
+      .cull_mode(match self.cull {
+        CullMode::None => rasterization::CullMode::None,
+        CullMode::Back => rasterization::CullMode::Back,
+        CullMode::Front => rasterization::CullMode::Front,
+      })
+  }
+
+  pub(super) fn depth_stencil_state(&self) -> DepthStencilState {
+    match self.depth {
+      DepthTest::Disabled => DepthStencilState::disabled(),
+      DepthTest::ReadOnly => DepthStencilState {
+        depth: Some(DepthState {
+          enable_dynamic: false,
+          write_enable: StateMode::Fixed(false),
+          compare_op: StateMode::Fixed(CompareOp::Less),
+        }),
+        ..DepthStencilState::disabled()
+      },
+      DepthTest::ReadWrite => DepthStencilState::simple_depth_test(),
+    }
+  }
+}
+
+// Opaque pipelines draw first, then transparent ones; within each group draws
+// are ordered by material so each pipeline is bound once.
+pub(super) fn get_draw_order(material: &Material) -> (bool, &Material) {
+  (material.is_transparent(), material)
+}
+
+#[cfg(test)]
+mod material_tests {
+  use super::*;
+
+  #[test]
+  fn test_draw_order() {
+    let opaque = Material::default();
+    let culled_front = Material::default().with_cull(CullMode::Front);
+    let transparent = Material::transparent(DEFAULT_SHADER);
+
+    let mut materials = vec![&transparent, &culled_front, &opaque];
+    materials.sort_by(|a, b| get_draw_order(a).cmp(&get_draw_order(b)));
+
+    assert_eq!(materials, vec![&opaque, &culled_front, &transparent]);
+  }
+
+  #[test]
+  fn test_pipeline_state() {
+    let material = Material::transparent(DEFAULT_SHADER).with_cull(CullMode::None);
+
+    assert!(material.color_blend_state().attachments[0].blend.is_some());
+    assert!(Material::default().color_blend_state().attachments[0]
+      .blend
+      .is_none());
+
+    let depth = material.depth_stencil_state().depth.unwrap();
+    assert_eq!(depth.write_enable, StateMode::Fixed(false));
+    assert!(Material::default()
+      .with_depth(DepthTest::Disabled)
+      .depth_stencil_state()
+      .depth
+      .is_none());
+
+    let rasterization = material.rasterization_state();
+    assert_eq!(
+      rasterization.front_face,
+      StateMode::Fixed(FrontFace::CounterClockwise)
+    );
+    assert_eq!(
+      rasterization.cull_mode,
+      StateMode::Fixed(rasterization::CullMode::None)
+    );
+  }
+}
@@ -0,0 +1,275 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector3};
+
+use super::vulkan::Vertex;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Indices {
+  U16(Vec<u16>),
+  U32(Vec<u32>),
+}
+
+impl Indices {
+  // Uses 16-bit indices whenever every index fits.
+  pub fn new(indices: Vec<u32>) -> Self {
+    match indices.iter().all(|&index| index <= u16::MAX as u32) {
+      true => Indices::U16(indices.into_iter().map(|index| index as u16).collect()),
+      false => Indices::U32(indices),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    match self {
+      Indices::U16(indices) => indices.len(),
+      Indices::U32(indices) => indices.len(),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn iter(&self) -> Box<dyn Iterator<Item = u32> + '_> {
+    match self {
+      Indices::U16(indices) => Box::new(indices.iter().map(|&index| index as u32)),
+      Indices::U32(indices) => Box::new(indices.iter().copied()),
+    }
+  }
+}
+
+// Primitives are wound counter-clockwise when seen from outside, in a
+// right-handed, y-up space, and are white until recolored with `with_color`.
+// UVs span 0..1 with v pointing down the image.
+#[derive(Clone, Debug)]
+pub struct Mesh {
+  pub vertices: Vec<Vertex>,
+  pub indices: Option<Indices>,
+}
+
+impl Mesh {
+  pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+    Self {
+      vertices,
+      indices: Some(Indices::new(indices)),
+    }
+  }
+
+  pub fn from_vertices(vertices: Vec<Vertex>) -> Self {
+    Self {
+      vertices,
+      indices: None,
+    }
+  }
+
+  pub fn with_color(mut self, color: [f32; 4]) -> Self {
+    for vertex in &mut self.vertices {
+      vertex.color = color;
+    }
+    self
+  }
+
+  // Replaces the normals with the area-weighted average of the faces around
+  // each vertex.
+  pub fn with_smooth_normals(mut self) -> Self {
+    let indices: Vec<u32> = match &self.indices {
+      Some(indices) => indices.iter().collect(),
+      None => (0..self.vertices.len() as u32).collect(),
+    };
+
+    let mut normals = vec![Vector3::new(0.0, 0.0, 0.0); self.vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+      let [a, b, c] =
+        [0, 1, 2].map(|i| Vector3::from(self.vertices[triangle[i] as usize].position));
+      let normal = (b - a).cross(c - a);
+      for &index in triangle {
+        normals[index as usize] += normal;
+      }
+    }
+
+    for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+      if normal.magnitude2() > 0.0 {
+        vertex.normal = normal.normalize().into();
+      }
+    }
+    self
+  }
+
+  pub fn quad(width: f32, height: f32) -> Self {
+    let (x, y) = (width / 2.0, height / 2.0);
+    let vertices = [[-x, -y, 0.0], [x, -y, 0.0], [x, y, 0.0], [-x, y, 0.0]]
+      .into_iter()
+      .zip(FACE_UVS)
+      .map(|(position, uv)| vertex(position, [0.0, 0.0, 1.0], uv))
+      .collect();
+
+    Self::new(vertices, vec![0, 1, 2, 2, 3, 0])
+  }
+
+  pub fn plane(size: f32, subdivisions: u32) -> Self {
+    let cells = subdivisions + 1;
+    let step = size / cells as f32;
+    let offset = size / 2.0;
+
+    let mut vertices = Vec::new();
+    for z in 0..=cells {
+      for x in 0..=cells {
+        vertices.push(vertex(
+          [x as f32 * step - offset, 0.0, z as f32 * step - offset],
+          [0.0, 1.0, 0.0],
+          [x as f32 / cells as f32, z as f32 / cells as f32],
+        ));
+      }
+    }
+
+    let mut indices = Vec::new();
+    let row = cells + 1;
+    for z in 0..cells {
+      for x in 0..cells {
+        let i = z * row + x;
+        indices.extend_from_slice(&[i, i + row, i + row + 1, i + row + 1, i + 1, i]);
+      }
+    }
+
+    Self::new(vertices, indices)
+  }
+
+  pub fn cube(size: f32) -> Self {
+    let h = size / 2.0;
+    // Each face gets its own four corners so later per-face attributes stay
+    // sharp: +x, -x, +y, -y, +z, -z.
+    let faces: [[[f32; 3]; 4]; 6] = [
+      [[h, -h, h], [h, -h, -h], [h, h, -h], [h, h, h]],
+      [[-h, -h, -h], [-h, -h, h], [-h, h, h], [-h, h, -h]],
+      [[-h, h, h], [h, h, h], [h, h, -h], [-h, h, -h]],
+      [[-h, -h, -h], [h, -h, -h], [h, -h, h], [-h, -h, h]],
+      [[-h, -h, h], [h, -h, h], [h, h, h], [-h, h, h]],
+      [[h, -h, -h], [-h, -h, -h], [-h, h, -h], [h, h, -h]],
+    ];
+
+    let normals = [
+      [1.0, 0.0, 0.0],
+      [-1.0, 0.0, 0.0],
+      [0.0, 1.0, 0.0],
+      [0.0, -1.0, 0.0],
+      [0.0, 0.0, 1.0],
+      [0.0, 0.0, -1.0],
+    ];
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for (face, normal) in faces.into_iter().zip(normals) {
+      let base = vertices.len() as u32;
+      vertices.extend(
+        face
+          .into_iter()
+          .zip(FACE_UVS)
+          .map(|(position, uv)| vertex(position, normal, uv)),
+      );
+      indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+    }
+
+    Self::new(vertices, indices)
+  }
+
+  pub fn sphere(radius: f32, segments: u32, rings: u32) -> Self {
+    let segments = segments.max(3);
+    let rings = rings.max(2);
+
+    let mut vertices = Vec::new();
+    for ring in 0..=rings {
+      let phi = PI * ring as f32 / rings as f32;
+      for segment in 0..=segments {
+        let theta = 2.0 * PI * segment as f32 / segments as f32;
+        let normal = [phi.sin() * theta.cos(), phi.cos(), -phi.sin() * theta.sin()];
+        vertices.push(vertex(
+          normal.map(|n| n * radius),
+          normal,
+          [segment as f32 / segments as f32, ring as f32 / rings as f32],
+        ));
+      }
+    }
+
+    let mut indices = Vec::new();
+    let row = segments + 1;
+    for ring in 0..rings {
+      for segment in 0..segments {
+        let i = ring * row + segment;
+        if ring != 0 {
+          indices.extend_from_slice(&[i, i + row, i + 1]);
+        }
+        if ring != rings - 1 {
+          indices.extend_from_slice(&[i + 1, i + row, i + row + 1]);
+        }
+      }
+    }
+
+    Self::new(vertices, indices)
+  }
+}
+
+// Corners of a face in the order quads and cube faces list them, starting at
+// the bottom left.
+const FACE_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+
+fn vertex(position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> Vertex {
+  Vertex {
+    position,
+    color: [1.0, 1.0, 1.0, 1.0],
+    uv,
+    normal,
+  }
+}
+
+#[cfg(test)]
+mod mesh_tests {
+  use super::*;
+
+  fn assert_valid(mesh: &Mesh, triangles: usize) {
+    let indices = mesh.indices.as_ref().unwrap();
+    assert_eq!(indices.len(), triangles * 3);
+    assert!(indices
+      .iter()
+      .all(|index| (index as usize) < mesh.vertices.len()));
+    assert!(mesh
+      .vertices
+      .iter()
+      .all(|vertex| vertex.uv.iter().all(|c| (0.0..=1.0).contains(c))));
+  }
+
+  #[test]
+  fn test_index_width() {
+    assert!(matches!(Indices::new(vec![0, 1, 65535]), Indices::U16(_)));
+    assert!(matches!(Indices::new(vec![0, 1, 65536]), Indices::U32(_)));
+  }
+
+  #[test]
+  fn test_smooth_normals() {
+    let mut mesh = Mesh::cube(1.0);
+    for vertex in &mut mesh.vertices {
+      vertex.normal = [0.0, 0.0, 0.0];
+    }
+
+    let mesh = mesh.with_smooth_normals();
+    assert_eq!(mesh.vertices[0].normal, [1.0, 0.0, 0.0]);
+    assert_eq!(mesh.vertices[20].normal, [0.0, 0.0, -1.0]);
+  }
+
+  #[test]
+  fn test_primitives() {
+    assert_valid(&Mesh::quad(1.0, 1.0), 2);
+    assert_valid(&Mesh::plane(1.0, 1), 8);
+    assert_valid(&Mesh::cube(1.0), 12);
+
+    let sphere = Mesh::sphere(1.0, 8, 4);
+    assert_valid(&sphere, 8 * 2 * (4 - 1));
+    assert!(sphere.vertices.iter().all(|vertex| {
+      let position = Vector3::from(vertex.position);
+      (position - Vector3::from(vertex.normal)).magnitude() < 1e-5
+    }));
+    assert!(sphere
+      .vertices
+      .iter()
+      .all(|vertex| (Vector3::from(vertex.position).magnitude() - 1.0).abs() < 1e-5));
+  }
+}
@@ -0,0 +1,372 @@
+use std::{
+  collections::HashMap,
+  error::Error,
+  fmt, fs, io,
+  path::{Path, PathBuf},
+};
+
+use cgmath::{InnerSpace, Vector3};
+
+use super::{mesh::Mesh, vulkan::Vertex};
+
+#[derive(Debug)]
+pub enum ObjError {
+  Io(PathBuf, io::Error),
+  Parse {
+    file: String,
+    line: usize,
+    message: String,
+  },
+}
+
+impl fmt::Display for ObjError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ObjError::Io(path, e) => write!(f, "couldn't read {:?}: {}", path, e),
+      ObjError::Parse {
+        file,
+        line,
+        message,
+      } => write!(f, "{}:{}: {}", file, line, message),
+    }
+  }
+}
+
+impl Error for ObjError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      ObjError::Io(_, e) => Some(e),
+      ObjError::Parse { .. } => None,
+    }
+  }
+}
+
+// Material libraries are resolved relative to the .obj file. Diffuse material
+// colors end up in the vertex colors.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<Mesh, ObjError> {
+  let path = path.as_ref();
+  let source = read(path)?;
+  let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+  parse_obj(&source, &path.display().to_string(), |library| {
+    let path = dir.join(library);
+    parse_mtl(&read(&path)?, &path.display().to_string())
+  })
+}
+
+// Polygons are triangulated as fans, so they're expected to be convex. Faces
+// without normals get a flat face normal.
+pub fn parse_obj(
+  source: &str,
+  file: &str,
+  mut load_materials: impl FnMut(&str) -> Result<HashMap<String, [f32; 4]>, ObjError>,
+) -> Result<Mesh, ObjError> {
+  let mut positions: Vec<[f32; 3]> = Vec::new();
+  let mut uvs: Vec<[f32; 2]> = Vec::new();
+  let mut normals: Vec<[f32; 3]> = Vec::new();
+  let mut materials = HashMap::new();
+  let mut color = [1.0, 1.0, 1.0, 1.0];
+
+  let mut vertices = Vec::new();
+  let mut indices = Vec::new();
+  let mut seen: HashMap<Vec<u8>, u32> = HashMap::new();
+
+  for (i, line) in source.lines().enumerate() {
+    let error = |message: String| ObjError::Parse {
+      file: file.to_string(),
+      line: i + 1,
+      message,
+    };
+
+    let line = line.split('#').next().unwrap_or("");
+    let mut parts = line.split_whitespace();
+    let Some(keyword) = parts.next() else {
+      continue;
+    };
+    let args: Vec<&str> = parts.collect();
+
+    match keyword {
+      "v" => positions.push(parse_floats(&args, 3).map_err(error)?),
+      "vt" => {
+        // OBJ puts v = 0 at the bottom of the image.
+        let [u, v] = parse_floats(&args, 1).map_err(error)?;
+        uvs.push([u, 1.0 - v]);
+      }
+      "vn" => normals.push(parse_floats(&args, 3).map_err(error)?),
+      "f" => {
+        if args.len() < 3 {
+          return Err(error(format!(
+            "a face needs at least 3 vertices, got {}",
+            args.len()
+          )));
+        }
+
+        let corners = args
+          .iter()
+          .map(|corner| parse_corner(corner, positions.len(), uvs.len(), normals.len()))
+          .collect::<Result<Vec<_>, _>>()
+          .map_err(error)?;
+
+        let face_normal = get_face_normal(
+          positions[corners[0].0],
+          positions[corners[1].0],
+          positions[corners[2].0],
+        );
+
+        let face: Vec<u32> = corners
+          .into_iter()
+          .map(|(position, uv, normal)| {
+            let vertex = Vertex {
+              position: positions[position],
+              color,
+              uv: uv.map(|uv| uvs[uv]).unwrap_or([0.0, 0.0]),
+              normal: normal.map(|normal| normals[normal]).unwrap_or(face_normal),
+            };
+
+            *seen
+              .entry(bytemuck::bytes_of(&vertex).to_vec())
+              .or_insert_with(|| {
+                vertices.push(vertex);
+                vertices.len() as u32 - 1
+              })
+          })
+          .collect();
+
+        for k in 1..face.len() - 1 {
+          indices.extend_from_slice(&[face[0], face[k], face[k + 1]]);
+        }
+      }
+      "mtllib" => materials.extend(load_materials(&args.join(" "))?),
+      "usemtl" => {
+        let name = args.join(" ");
+        color = match materials.get(&name) {
+          Some(color) => *color,
+          None => return Err(error(format!("unknown material {:?}", name))),
+        };
+      }
+      _ => (),
+    }
+  }
+
+  Ok(Mesh::new(vertices, indices))
+}
+
+// Returns each material's diffuse color, with `d` or `Tr` as alpha.
+pub fn parse_mtl(source: &str, file: &str) -> Result<HashMap<String, [f32; 4]>, ObjError> {
+  let mut materials = HashMap::new();
+  let mut current: Option<String> = None;
+
+  for (i, line) in source.lines().enumerate() {
+    let error = |message: String| ObjError::Parse {
+      file: file.to_string(),
+      line: i + 1,
+      message,
+    };
+
+    let line = line.split('#').next().unwrap_or("");
+    let mut parts = line.split_whitespace();
+    let Some(keyword) = parts.next() else {
+      continue;
+    };
+    let args: Vec<&str> = parts.collect();
+
+    if keyword == "newmtl" {
+      let name = args.join(" ");
+      materials.insert(name.clone(), [1.0, 1.0, 1.0, 1.0]);
+      current = Some(name);
+      continue;
+    }
+
+    if !matches!(keyword, "Kd" | "d" | "Tr") {
+      continue;
+    }
+
+    let Some(material) = current.as_ref().and_then(|name| materials.get_mut(name)) else {
+      return Err(error(format!("{} before any newmtl", keyword)));
+    };
+
+    match keyword {
+      "Kd" => {
+        let [r, g, b] = parse_floats(&args, 3).map_err(error)?;
+        material[..3].copy_from_slice(&[r, g, b]);
+      }
+      "d" => material[3] = parse_floats::<1>(&args, 1).map_err(error)?[0],
+      _ => material[3] = 1.0 - parse_floats::<1>(&args, 1).map_err(error)?[0],
+    }
+  }
+
+  Ok(materials)
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+  fs::read_to_string(path).map_err(|e| ObjError::Io(path.to_path_buf(), e))
+}
+
+// Parses up to N floats, requiring at least `required`; missing ones are zero.
+fn parse_floats<const N: usize>(args: &[&str], required: usize) -> Result<[f32; N], String> {
+  if args.len() < required {
+    return Err(format!("expected {} numbers, got {}", required, args.len()));
+  }
+
+  let mut values = [0.0; N];
+  for (value, arg) in values.iter_mut().zip(args) {
+    *value = arg
+      .parse()
+      .map_err(|_| format!("invalid number {:?}", arg))?;
+  }
+
+  Ok(values)
+}
+
+// Resolves a `v`, `v/vt`, `v//vn` or `v/vt/vn` corner into zero-based indices.
+fn parse_corner(
+  corner: &str,
+  positions: usize,
+  uvs: usize,
+  normals: usize,
+) -> Result<(usize, Option<usize>, Option<usize>), String> {
+  let mut parts = corner.split('/');
+  let position = parse_index(parts.next(), positions, "position")?;
+  let uv = parse_index(parts.next(), uvs, "texture coordinate")?;
+  let normal = parse_index(parts.next(), normals, "normal")?;
+
+  match position {
+    Some(position) => Ok((position, uv, normal)),
+    None => Err(format!("face vertex {:?} has no position", corner)),
+  }
+}
+
+// OBJ indices start at 1, and negative ones count back from the latest element.
+fn parse_index(index: Option<&str>, count: usize, kind: &str) -> Result<Option<usize>, String> {
+  let index = match index {
+    Some(index) if !index.is_empty() => index,
+    _ => return Ok(None),
+  };
+
+  let value: i64 = index
+    .parse()
+    .map_err(|_| format!("invalid {} index {:?}", kind, index))?;
+
+  let resolved = match value {
+    0 => None,
+    value if value > 0 => Some(value - 1),
+    value => Some(count as i64 + value),
+  };
+
+  match resolved {
+    Some(resolved) if resolved >= 0 && (resolved as usize) < count => Ok(Some(resolved as usize)),
+    _ => Err(format!(
+      "{} index {} is out of range, there are {}",
+      kind, value, count
+    )),
+  }
+}
+
+fn get_face_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
+  let (a, b, c) = (Vector3::from(a), Vector3::from(b), Vector3::from(c));
+  let normal = (b - a).cross(c - a);
+
+  match normal.magnitude2() > 0.0 {
+    true => normal.normalize().into(),
+    false => [0.0, 0.0, 0.0],
+  }
+}
+
+#[cfg(test)]
+mod obj_tests {
+  use super::*;
+
+  fn no_materials(library: &str) -> Result<HashMap<String, [f32; 4]>, ObjError> {
+    panic!("unexpected material library {}", library)
+  }
+
+  #[test]
+  fn test_parse_quad() {
+    let source = "
+      # a unit quad facing +z
+      v 0 0 0
+      v 1 0 0
+      v 1 1 0
+      v 0 1 0
+      vt 0 0
+      vt 1 0
+      vt 1 1
+      vt 0 1
+      vn 0 0 1
+      f 1/1/1 2/2/1 3/3/1 4/4/1
+    ";
+
+    let mesh = parse_obj(source, "quad.obj", no_materials).unwrap();
+    assert_eq!(mesh.vertices.len(), 4);
+    assert_eq!(
+      mesh.indices.unwrap().iter().collect::<Vec<_>>(),
+      vec![0, 1, 2, 0, 2, 3]
+    );
+    assert_eq!(mesh.vertices[0].uv, [0.0, 1.0]);
+    assert_eq!(mesh.vertices[2].normal, [0.0, 0.0, 1.0]);
+  }
+
+  #[test]
+  fn test_deduplicate_and_face_normals() {
+    let source = "
+      v 0 0 0
+      v 1 0 0
+      v 1 1 0
+      v 0 1 0
+      f 1 2 3
+      f -4 -2 -1
+    ";
+
+    let mesh = parse_obj(source, "quad.obj", no_materials).unwrap();
+    assert_eq!(mesh.vertices.len(), 4);
+    assert_eq!(mesh.indices.unwrap().len(), 6);
+    assert!(mesh
+      .vertices
+      .iter()
+      .all(|vertex| vertex.normal == [0.0, 0.0, 1.0]));
+  }
+
+  #[test]
+  fn test_materials() {
+    let mtl = "
+      newmtl red
+      Kd 1 0 0
+      d 0.5
+    ";
+    let source = "
+      mtllib scene.mtl
+      v 0 0 0
+      v 1 0 0
+      v 0 1 0
+      usemtl red
+      f 1 2 3
+    ";
+
+    let mesh = parse_obj(source, "scene.obj", |library| {
+      assert_eq!(library, "scene.mtl");
+      parse_mtl(mtl, library)
+    })
+    .unwrap();
+
+    assert!(mesh
+      .vertices
+      .iter()
+      .all(|vertex| vertex.color == [1.0, 0.0, 0.0, 0.5]));
+  }
+
+  #[test]
+  fn test_errors_report_lines() {
+    let source = "v 0 0 0\nv 1 0 0\nf 1 2 3\n";
+    let error = parse_obj(source, "broken.obj", no_materials).unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      "broken.obj:3: position index 3 is out of range, there are 2"
+    );
+
+    let error = parse_obj("v 0 zero 0", "broken.obj", no_materials).unwrap_err();
+    assert_eq!(error.to_string(), "broken.obj:1: invalid number \"zero\"");
+
+    let error = parse_mtl("Kd 1 1 1", "broken.mtl").unwrap_err();
+    assert_eq!(error.to_string(), "broken.mtl:1: Kd before any newmtl");
+  }
+}
@@ -0,0 +1,501 @@
+use std::{collections::HashMap, error::Error, sync::Arc};
+
+use vulkano::{
+  command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+  descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+  device::Device,
+  format::Format,
+  pipeline::{
+    graphics::{
+      input_assembly::InputAssemblyState, vertex_input::BuffersDefinition, viewport::ViewportState,
+    },
+    GraphicsPipeline, Pipeline, PipelineBindPoint,
+  },
+  render_pass::Subpass,
+  sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
+  shader::ShaderModule,
+};
+
+use super::graph::{
+  AttachmentDesc, AttachmentSize, GraphError, PassContext, PassDesc, RenderGraph, TARGET,
+};
+
+// The scene is drawn into this, and the post passes bring it to the target.
+pub const HDR: &str = "hdr";
+pub const HDR_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+
+// Tonemapped colors, when FXAA still has to run on them.
+const LDR: &str = "ldr";
+const LDR_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+// Bloom is extracted and blurred at half resolution.
+const BLOOM_BRIGHT: &str = "bloom_bright";
+const BLOOM_BLURRED_X: &str = "bloom_blurred_x";
+const BLOOM: &str = "bloom";
+
+const EXTRACT_PASS: &str = "bloom_extract";
+const BLUR_X_PASS: &str = "bloom_blur_x";
+const BLUR_Y_PASS: &str = "bloom_blur_y";
+const TONEMAP_PASS: &str = "tonemap";
+const FXAA_PASS: &str = "fxaa";
+
+mod post_vs {
+  vulkano_shaders::shader! {
+    ty: "vertex",
+    path: "shaders/post.vert",
+  }
+}
+
+mod bloom_fs {
+  vulkano_shaders::shader! {
+    ty: "fragment",
+    path: "shaders/bloom.frag",
+    types_meta: {
+      use bytemuck::{Pod, Zeroable};
+
+      #[derive(Clone, Copy, Zeroable, Pod)]
+    },
+  }
+}
+
+mod tonemap_fs {
+  vulkano_shaders::shader! {
+    ty: "fragment",
+    path: "shaders/tonemap.frag",
+    types_meta: {
+      use bytemuck::{Pod, Zeroable};
+
+      #[derive(Clone, Copy, Zeroable, Pod)]
+    },
+  }
+}
+
+mod fxaa_fs {
+  vulkano_shaders::shader! {
+    ty: "fragment",
+    path: "shaders/fxaa.frag",
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tonemap {
+  // Colors are only clamped.
+  None,
+  Reinhard,
+  Aces,
+}
+
+impl Tonemap {
+  // Matches the TONEMAP_* constants in shaders/tonemap.frag.
+  fn mode(self) -> i32 {
+    match self {
+      Tonemap::None => 0,
+      Tonemap::Reinhard => 1,
+      Tonemap::Aces => 2,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BloomOptions {
+  // Brightness above which pixels bleed into their surroundings.
+  pub threshold: f32,
+  pub intensity: f32,
+}
+
+impl Default for BloomOptions {
+  fn default() -> Self {
+    Self {
+      threshold: 1.0,
+      intensity: 0.5,
+    }
+  }
+}
+
+// The defaults leave the scene's colors as they are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PostOptions {
+  pub exposure: f32,
+  pub tonemap: Tonemap,
+  // Colors are raised to 1 / gamma. sRGB targets encode on their own, so keep
+  // it at 1 for those.
+  pub gamma: f32,
+  pub bloom: Option<BloomOptions>,
+  pub fxaa: bool,
+  // How much the corners are darkened, from 0 to 1.
+  pub vignette: f32,
+}
+
+impl Default for PostOptions {
+  fn default() -> Self {
+    Self {
+      exposure: 1.0,
+      tonemap: Tonemap::None,
+      gamma: 1.0,
+      bloom: None,
+      fxaa: false,
+      vignette: 0.0,
+    }
+  }
+}
+
+// Owns the full-screen passes between the HDR target and the final target.
+// Turning bloom or FXAA on or off changes the passes and recompiles the graph;
+// anything else only changes push constants.
+pub(super) struct PostStack {
+  options: PostOptions,
+  vertex: Arc<ShaderModule>,
+  bloom: Arc<ShaderModule>,
+  tonemap: Arc<ShaderModule>,
+  fxaa: Arc<ShaderModule>,
+  sampler: Arc<Sampler>,
+  pipelines: HashMap<&'static str, Arc<GraphicsPipeline>>,
+}
+
+impl PostStack {
+  // Adds the post attachments and passes to the graph and compiles it.
+  pub(super) fn new(
+    device: Arc<Device>,
+    graph: &mut RenderGraph,
+    options: PostOptions,
+  ) -> Result<Self, Box<dyn Error>> {
+    validate_options(&options)?;
+
+    graph.add_attachment(LDR, AttachmentDesc::new(LDR_FORMAT))?;
+    for name in [BLOOM_BRIGHT, BLOOM_BLURRED_X, BLOOM] {
+      graph.add_attachment(
+        name,
+        AttachmentDesc::new(HDR_FORMAT).with_size(AttachmentSize::Relative(0.5)),
+      )?;
+    }
+
+    let sampler = Sampler::new(
+      device.clone(),
+      SamplerCreateInfo {
+        mag_filter: Filter::Linear,
+        min_filter: Filter::Linear,
+        address_mode: [SamplerAddressMode::ClampToEdge; 3],
+        ..Default::default()
+      },
+    )?;
+
+    let mut stack = Self {
+      options,
+      vertex: post_vs::load(device.clone())?,
+      bloom: bloom_fs::load(device.clone())?,
+      tonemap: tonemap_fs::load(device.clone())?,
+      fxaa: fxaa_fs::load(device.clone())?,
+      sampler,
+      pipelines: HashMap::new(),
+    };
+
+    for pass in get_post_passes(&options) {
+      graph.add_pass(pass, None)?;
+    }
+    graph.compile()?;
+    stack.pipelines = stack.get_pipelines(device, graph)?;
+
+    Ok(stack)
+  }
+
+  pub(super) fn options(&self) -> PostOptions {
+    self.options
+  }
+
+  pub(super) fn set_options(
+    &mut self,
+    device: Arc<Device>,
+    graph: &mut RenderGraph,
+    options: PostOptions,
+  ) -> Result<(), Box<dyn Error>> {
+    validate_options(&options)?;
+
+    let passes = get_post_passes(&options);
+    let previous = get_post_passes(&self.options);
+    let index = match passes != previous {
+      true => {
+        // The passes go back where the old ones were, so passes added after
+        // the stack still draw over the tonemapped image.
+        let index = graph
+          .pass_index(&previous[0].name)
+          .ok_or("the render graph has no post passes")?;
+        replace_passes(graph, index, &previous, passes.clone())?;
+        Some(index)
+      }
+      false => None,
+    };
+
+    let pipelines = match index {
+      Some(_) => match graph.compile() {
+        Ok(()) => self.get_pipelines(device, graph),
+        Err(e) => Err(e.into()),
+      },
+      None => self.get_pipelines(device, graph),
+    };
+
+    match pipelines {
+      Ok(pipelines) => {
+        self.pipelines = pipelines;
+        self.options = options;
+        Ok(())
+      }
+      Err(e) => {
+        if let Some(index) = index {
+          replace_passes(graph, index, &passes, previous)?;
+          graph.compile()?;
+        }
+        Err(e)
+      }
+    }
+  }
+
+  pub(super) fn record(
+    &self,
+    pass: &PassContext,
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+  ) -> Result<(), Box<dyn Error>> {
+    let Some((name, pipeline)) = self.pipelines.get_key_value(pass.name.as_str()) else {
+      return Err(format!("no recorder for pass {:?}", pass.name).into());
+    };
+    let layout = pipeline.layout().clone();
+    let options = self.options;
+
+    builder
+      .set_viewport(0, [pass.viewport.clone()])
+      .bind_pipeline_graphics(pipeline.clone());
+
+    let inputs = match *name {
+      EXTRACT_PASS | BLUR_X_PASS | BLUR_Y_PASS => {
+        let (input, direction) = match *name {
+          EXTRACT_PASS => (HDR, [0.0, 0.0]),
+          BLUR_X_PASS => (BLOOM_BRIGHT, [1.0, 0.0]),
+          _ => (BLOOM_BLURRED_X, [0.0, 1.0]),
+        };
+        builder.push_constants(
+          layout.clone(),
+          0,
+          bloom_fs::ty::Bloom {
+            direction,
+            threshold: options.bloom.unwrap_or_default().threshold,
+          },
+        );
+        vec![input]
+      }
+      TONEMAP_PASS => {
+        builder.push_constants(
+          layout.clone(),
+          0,
+          tonemap_fs::ty::Tonemap {
+            exposure: options.exposure,
+            gamma: options.gamma,
+            bloom_intensity: options.bloom.map_or(0.0, |bloom| bloom.intensity),
+            vignette: options.vignette,
+            mode: options.tonemap.mode(),
+          },
+        );
+        // Without bloom the HDR target stands in for it, at zero intensity.
+        match options.bloom {
+          Some(_) => vec![HDR, BLOOM],
+          None => vec![HDR, HDR],
+        }
+      }
+      _ => vec![LDR],
+    };
+
+    let mut writes = Vec::new();
+    for (binding, input) in inputs.into_iter().enumerate() {
+      let Some(view) = pass.inputs.get(input) else {
+        return Err(format!("pass {:?} doesn't read {:?}", pass.name, input).into());
+      };
+      writes.push(WriteDescriptorSet::image_view_sampler(
+        binding as u32,
+        view.clone(),
+        self.sampler.clone(),
+      ));
+    }
+
+    let set = PersistentDescriptorSet::new(layout.set_layouts()[0].clone(), writes)?;
+    builder
+      .bind_descriptor_sets(PipelineBindPoint::Graphics, layout, 0, set)
+      .draw(3, 1, 0, 0)?;
+
+    Ok(())
+  }
+
+  fn get_pipelines(
+    &self,
+    device: Arc<Device>,
+    graph: &RenderGraph,
+  ) -> Result<HashMap<&'static str, Arc<GraphicsPipeline>>, Box<dyn Error>> {
+    let mut pipelines = HashMap::new();
+    for name in [
+      EXTRACT_PASS,
+      BLUR_X_PASS,
+      BLUR_Y_PASS,
+      TONEMAP_PASS,
+      FXAA_PASS,
+    ] {
+      let Some(subpass) = graph.subpass(name) else {
+        continue;
+      };
+
+      let fragment = match name {
+        TONEMAP_PASS => &self.tonemap,
+        FXAA_PASS => &self.fxaa,
+        _ => &self.bloom,
+      };
+      pipelines.insert(
+        name,
+        get_post_pipeline(device.clone(), &self.vertex, fragment, subpass)?,
+      );
+    }
+
+    Ok(pipelines)
+  }
+}
+
+fn validate_options(options: &PostOptions) -> Result<(), Box<dyn Error>> {
+  if !options.gamma.is_finite() || options.gamma <= 0.0 {
+    return Err("gamma has to be a finite number above 0".into());
+  }
+  if !options.exposure.is_finite() || options.exposure < 0.0 {
+    return Err("exposure has to be a finite number of at least 0".into());
+  }
+  if !(0.0..=1.0).contains(&options.vignette) {
+    return Err("vignette has to be between 0 and 1".into());
+  }
+  if let Some(bloom) = options.bloom {
+    if !bloom.threshold.is_finite() || !bloom.intensity.is_finite() {
+      return Err("bloom threshold and intensity have to be finite".into());
+    }
+  }
+  Ok(())
+}
+
+fn replace_passes(
+  graph: &mut RenderGraph,
+  index: usize,
+  old: &[PassDesc],
+  new: Vec<PassDesc>,
+) -> Result<(), GraphError> {
+  for pass in old {
+    graph.remove_pass(&pass.name)?;
+  }
+  for (offset, pass) in new.into_iter().enumerate() {
+    graph.insert_pass(index + offset, pass, None)?;
+  }
+  Ok(())
+}
+
+// Tonemapping always runs, since it's what moves the HDR target into the
+// final one.
+fn get_post_passes(options: &PostOptions) -> Vec<PassDesc> {
+  let mut passes = Vec::new();
+
+  let mut tonemap = PassDesc::new(TONEMAP_PASS).read(HDR);
+  if options.bloom.is_some() {
+    passes.push(PassDesc::new(EXTRACT_PASS).read(HDR).color(BLOOM_BRIGHT));
+    passes.push(
+      PassDesc::new(BLUR_X_PASS)
+        .read(BLOOM_BRIGHT)
+        .color(BLOOM_BLURRED_X),
+    );
+    passes.push(
+      PassDesc::new(BLUR_Y_PASS)
+        .read(BLOOM_BLURRED_X)
+        .color(BLOOM),
+    );
+    tonemap = tonemap.read(BLOOM);
+  }
+
+  match options.fxaa {
+    true => {
+      passes.push(tonemap.color(LDR));
+      passes.push(PassDesc::new(FXAA_PASS).read(LDR).color(TARGET));
+    }
+    false => passes.push(tonemap.color(TARGET)),
+  }
+
+  passes
+}
+
+fn get_post_pipeline(
+  device: Arc<Device>,
+  vertex: &Arc<ShaderModule>,
+  fragment: &Arc<ShaderModule>,
+  subpass: Subpass,
+) -> Result<Arc<GraphicsPipeline>, Box<dyn Error>> {
+  let (Some(vs), Some(fs)) = (vertex.entry_point("main"), fragment.entry_point("main")) else {
+    return Err("post shaders need a main entry point".into());
+  };
+
+  Ok(
+    GraphicsPipeline::start()
+      .vertex_input_state(BuffersDefinition::new())
+      .vertex_shader(vs, ())
+      .input_assembly_state(InputAssemblyState::new())
+      .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+      .fragment_shader(fs, ())
+      .render_pass(subpass)
+      .build(device)?,
+  )
+}
+
+#[cfg(test)]
+mod post_tests {
+  use super::*;
+
+  #[test]
+  fn test_post_passes() {
+    let passes = get_post_passes(&PostOptions::default());
+    assert_eq!(
+      passes,
+      vec![PassDesc::new(TONEMAP_PASS).read(HDR).color(TARGET)]
+    );
+
+    let passes = get_post_passes(&PostOptions {
+      bloom: Some(BloomOptions::default()),
+      fxaa: true,
+      ..Default::default()
+    });
+    let names: Vec<&str> = passes.iter().map(|pass| pass.name.as_str()).collect();
+    assert_eq!(
+      names,
+      vec![
+        EXTRACT_PASS,
+        BLUR_X_PASS,
+        BLUR_Y_PASS,
+        TONEMAP_PASS,
+        FXAA_PASS
+      ]
+    );
+    assert_eq!(passes[3].reads, vec![HDR, BLOOM]);
+    assert_eq!(passes[3].colors, vec![LDR]);
+    assert_eq!(passes[4].colors, vec![TARGET]);
+  }
+
+  #[test]
+  fn test_validate_options() {
+    assert!(validate_options(&PostOptions::default()).is_ok());
+    assert!(validate_options(&PostOptions {
+      gamma: 0.0,
+      ..Default::default()
+    })
+    .is_err());
+    assert!(validate_options(&PostOptions {
+      gamma: f32::NAN,
+      ..Default::default()
+    })
+    .is_err());
+    assert!(validate_options(&PostOptions {
+      exposure: f32::NAN,
+      ..Default::default()
+    })
+    .is_err());
+    assert!(validate_options(&PostOptions {
+      vignette: f32::NAN,
+      ..Default::default()
+    })
+    .is_err());
+  }
+}
@@ -0,0 +1,561 @@
+use std::{collections::HashMap, path::Path};
+
+use cgmath::{Matrix4, SquareMatrix};
+use gltf::{
+  buffer,
+  image::{self as gltf_image, Format},
+  material::AlphaMode,
+  mesh::Mode,
+  texture::{MagFilter, MinFilter, WrappingMode},
+  Node, Primitive,
+};
+use image::{DynamicImage, GrayAlphaImage, GrayImage, RgbImage, RgbaImage};
+
+use super::{
+  error::RendererError,
+  material::{CullMode, Material, DEFAULT_SHADER},
+  mesh::Mesh,
+  texture::{Filter, SamplerOptions, WrapMode},
+  vulkan::{InstanceData, Vertex, VulkanBackend},
+};
+use crate::{
+  ecs::components::{get_world_matrix, MeshRenderer, Skin, Transform},
+  registry::entity_registry::EntityRegistry,
+};
+
+pub struct GltfScene {
+  pub roots: Vec<String>,
+  pub entities: Vec<String>,
+  // Primitives that aren't triangle lists, named like their actors would be.
+  pub skipped: Vec<String>,
+}
+
+// What an import has made so far, so a failed one can take it out again.
+// Entities and materials that already existed are left alone.
+#[derive(Default)]
+struct Imported {
+  entities: Vec<String>,
+  actors: Vec<String>,
+  textures: Vec<String>,
+  materials: Vec<String>,
+}
+
+impl Imported {
+  fn remove(self, entities: &mut EntityRegistry, renderer: &mut VulkanBackend) {
+    for entity in &self.entities {
+      entities.remove_entity(entity);
+    }
+    // Everything here was created by the import, so none of these can fail.
+    for actor in self.actors {
+      let _ = renderer.remove_actor(actor);
+    }
+    for texture in self.textures {
+      let _ = renderer.remove_texture(texture);
+    }
+    for material in self.materials {
+      let _ = renderer.remove_material(material);
+    }
+  }
+}
+
+// Every node becomes an entity named `<prefix>/<node name>#<node index>`, and
+// every triangle primitive an actor named `<entity>.<primitive index>`. The
+// prefix defaults to the file stem. Buffers and images may be embedded or
+// sit next to the file, but are never fetched from the network. A failed
+// import removes whatever it had created.
+pub fn import_gltf(
+  path: impl AsRef<Path>,
+  prefix: Option<String>,
+  entities: &mut EntityRegistry,
+  renderer: &mut VulkanBackend,
+) -> Result<GltfScene, RendererError> {
+  let path = path.as_ref();
+  let prefix = match prefix {
+    Some(prefix) => prefix,
+    None => path
+      .file_stem()
+      .map(|stem| stem.to_string_lossy().into_owned())
+      .unwrap_or_else(|| String::from("gltf")),
+  };
+
+  let mut imported = Imported::default();
+  let result = import_document(path, &prefix, entities, renderer, &mut imported);
+  if result.is_err() {
+    imported.remove(entities, renderer);
+  }
+  result
+}
+
+fn import_document(
+  path: &Path,
+  prefix: &str,
+  entities: &mut EntityRegistry,
+  renderer: &mut VulkanBackend,
+  imported: &mut Imported,
+) -> Result<GltfScene, RendererError> {
+  let (document, buffers, images) = gltf::import(path)?;
+
+  let roots: Vec<Node> = match document
+    .default_scene()
+    .or_else(|| document.scenes().next())
+  {
+    Some(scene) => scene.nodes().collect(),
+    None => {
+      let children: Vec<usize> = document
+        .nodes()
+        .flat_map(|node| node.children().map(|child| child.index()))
+        .collect();
+      document
+        .nodes()
+        .filter(|node| !children.contains(&node.index()))
+        .collect()
+    }
+  };
+
+  let mut scene = GltfScene {
+    roots: Vec::new(),
+    entities: Vec::new(),
+    skipped: Vec::new(),
+  };
+  let mut node_entities = HashMap::new();
+  let mut textures = HashMap::new();
+  let mut materials = HashMap::new();
+  let mut skinned = Vec::new();
+
+  let mut stack: Vec<_> = roots.into_iter().rev().map(|node| (node, None)).collect();
+
+  while let Some((node, parent)) = stack.pop() {
+    let entity = format!(
+      "{}/{}#{}",
+      prefix,
+      node.name().unwrap_or("node"),
+      node.index()
+    );
+
+    let (translation, rotation, scale) = node.transform().decomposed();
+    let transform = Transform {
+      translation,
+      rotation,
+      scale,
+    };
+    if entities.get_component::<Transform>(&entity).is_none() {
+      imported.entities.push(entity.clone());
+    }
+    entities.add_component(entity.clone(), transform);
+    entities.set_parent(entity.clone(), parent.clone());
+
+    if let Some(mesh) = node.mesh() {
+      let mut actors = Vec::new();
+
+      for primitive in mesh.primitives() {
+        if primitive.mode() != Mode::Triangles {
+          scene
+            .skipped
+            .push(format!("{}.{}", entity, primitive.index()));
+          continue;
+        }
+
+        let material = primitive.material();
+        let pbr = material.pbr_metallic_roughness();
+        let base_color_texture = pbr.base_color_texture();
+        let tex_coord = base_color_texture
+          .as_ref()
+          .map(|info| info.tex_coord())
+          .unwrap_or(0);
+
+        let actor = renderer.create_actor(Some(format!("{}.{}", entity, primitive.index())))?;
+        imported.actors.push(actor.clone());
+        renderer.upload_mesh(actor.clone(), get_mesh(&primitive, &buffers, tex_coord)?)?;
+
+        // The node's transform stays on its entity and reaches the actor
+        // through `sync_transforms`. The material's base color goes in through
+        // the actor's instance.
+        renderer.set_actor_instances(
+          actor.clone(),
+          vec![InstanceData::new(
+            Matrix4::identity().into(),
+            pbr.base_color_factor(),
+          )],
+        )?;
+
+        if let Some(info) = base_color_texture {
+          let texture = info.texture();
+          let name = match textures.get(&texture.index()) {
+            Some(name) => name.clone(),
+            None => {
+              let source = texture.source().index();
+              let image = match images.get(source).and_then(get_image) {
+                Some(image) => image,
+                None => {
+                  return Err(RendererError::Import(format!(
+                    "image {} has an unsupported pixel format",
+                    source
+                  )))
+                }
+              };
+
+              let name = renderer.create_texture(
+                Some(format!("{}/texture{}", prefix, texture.index())),
+                &image,
+                get_sampler_options(&texture.sampler()),
+              )?;
+              imported.textures.push(name.clone());
+              textures.insert(texture.index(), name.clone());
+              name
+            }
+          };
+
+          renderer.set_actor_texture(actor.clone(), Some(name))?;
+        }
+
+        let state = get_material(&material);
+        if state != Material::default() {
+          // The default glTF material has no index.
+          let name = match materials.get(&material.index()) {
+            Some(name) => name.clone(),
+            None => {
+              let name = match material.index() {
+                Some(index) => format!("{}/material{}", prefix, index),
+                None => format!("{}/material", prefix),
+              };
+              if renderer.material(&name).is_none() {
+                imported.materials.push(name.clone());
+              }
+              renderer.create_material(name.clone(), state)?;
+              materials.insert(material.index(), name.clone());
+              name
+            }
+          };
+
+          renderer.set_actor_material(actor.clone(), name)?;
+        }
+
+        actors.push(actor);
+      }
+
+      entities.add_component(entity.clone(), MeshRenderer { actors });
+    }
+
+    if let Some(skin) = node.skin() {
+      skinned.push((entity.clone(), skin));
+    }
+
+    for child in node.children().collect::<Vec<_>>().into_iter().rev() {
+      stack.push((child, Some(entity.clone())));
+    }
+
+    if parent.is_none() {
+      scene.roots.push(entity.clone());
+    }
+    node_entities.insert(node.index(), entity.clone());
+    scene.entities.push(entity);
+  }
+
+  // Joints can live anywhere in the hierarchy, so skins are resolved once
+  // every node has an entity. Vertex skinning itself isn't applied yet.
+  for (entity, skin) in skinned {
+    let inverse_bind_matrices = skin
+      .reader(|buffer| Some(&buffers[buffer.index()]))
+      .read_inverse_bind_matrices()
+      .map(|matrices| matrices.collect())
+      .unwrap_or_default();
+
+    entities.add_component(
+      entity,
+      Skin {
+        joints: skin
+          .joints()
+          .filter_map(|joint| node_entities.get(&joint.index()).cloned())
+          .collect(),
+        inverse_bind_matrices,
+        skeleton: skin
+          .skeleton()
+          .and_then(|node| node_entities.get(&node.index()).cloned()),
+      },
+    );
+  }
+
+  sync_transforms(entities, renderer)?;
+  Ok(scene)
+}
+
+// Places every MeshRenderer's actors at their entity's world transform. Call
+// once per frame, after game code has moved entities.
+pub fn sync_transforms(
+  entities: &EntityRegistry,
+  renderer: &mut VulkanBackend,
+) -> Result<(), RendererError> {
+  let Some(meshes) = entities.get_entities_by_component::<MeshRenderer>() else {
+    return Ok(());
+  };
+
+  for entity in meshes {
+    let world = get_world_matrix(entities, entity);
+    if let Some(mesh) = entities.get_component::<MeshRenderer>(entity) {
+      for actor in &mesh.actors {
+        renderer.set_actor_world_matrix(actor.clone(), world)?;
+      }
+    }
+  }
+
+  Ok(())
+}
+
+fn get_mesh(
+  primitive: &Primitive,
+  buffers: &[buffer::Data],
+  tex_coord: u32,
+) -> Result<Mesh, RendererError> {
+  let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+  let Some(positions) = reader.read_positions() else {
+    return Err(RendererError::Import(format!(
+      "primitive {} has no positions",
+      primitive.index()
+    )));
+  };
+
+  let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|normals| normals.collect());
+  let uvs: Option<Vec<[f32; 2]>> = reader
+    .read_tex_coords(tex_coord)
+    .map(|uvs| uvs.into_f32().collect());
+  let colors: Option<Vec<[f32; 4]>> = reader
+    .read_colors(0)
+    .map(|colors| colors.into_rgba_f32().collect());
+
+  let vertices = positions
+    .enumerate()
+    .map(|(i, position)| Vertex {
+      position,
+      color: get_attribute(&colors, i).unwrap_or([1.0, 1.0, 1.0, 1.0]),
+      uv: get_attribute(&uvs, i).unwrap_or([0.0, 0.0]),
+      normal: get_attribute(&normals, i).unwrap_or([0.0, 0.0, 0.0]),
+    })
+    .collect();
+
+  let mesh = match reader.read_indices() {
+    Some(indices) => Mesh::new(vertices, indices.into_u32().collect()),
+    None => Mesh::from_vertices(vertices),
+  };
+
+  Ok(match normals {
+    Some(_) => mesh,
+    None => mesh.with_smooth_normals(),
+  })
+}
+
+fn get_attribute<T: Copy>(values: &Option<Vec<T>>, index: usize) -> Option<T> {
+  values.as_ref()?.get(index).copied()
+}
+
+fn get_image(data: &gltf_image::Data) -> Option<DynamicImage> {
+  let (width, height, pixels) = (data.width, data.height, data.pixels.clone());
+
+  match data.format {
+    Format::R8 => GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+    Format::R8G8 => GrayAlphaImage::from_raw(width, height, pixels).map(DynamicImage::ImageLumaA8),
+    Format::R8G8B8 => RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+    Format::R8G8B8A8 => RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8),
+    _ => None,
+  }
+}
+
+// Masked materials are drawn opaque, since the default shader has no alpha
+// cutoff.
+fn get_material(material: &gltf::Material) -> Material {
+  let state = match material.alpha_mode() {
+    AlphaMode::Blend => Material::transparent(DEFAULT_SHADER),
+    AlphaMode::Opaque | AlphaMode::Mask => Material::default(),
+  };
+
+  match material.double_sided() {
+    true => state.with_cull(CullMode::None),
+    false => state,
+  }
+}
+
+fn get_sampler_options(sampler: &gltf::texture::Sampler) -> SamplerOptions {
+  let (min_filter, mipmap_filter) = match sampler.min_filter() {
+    Some(MinFilter::Nearest) | Some(MinFilter::NearestMipmapNearest) => {
+      (Filter::Nearest, Filter::Nearest)
+    }
+    Some(MinFilter::LinearMipmapNearest) => (Filter::Linear, Filter::Nearest),
+    Some(MinFilter::NearestMipmapLinear) => (Filter::Nearest, Filter::Linear),
+    _ => (Filter::Linear, Filter::Linear),
+  };
+
+  SamplerOptions {
+    mag_filter: match sampler.mag_filter() {
+      Some(MagFilter::Nearest) => Filter::Nearest,
+      _ => Filter::Linear,
+    },
+    min_filter,
+    mipmap_filter,
+    wrap: [sampler.wrap_s(), sampler.wrap_t()].map(|wrap| match wrap {
+      WrappingMode::ClampToEdge => WrapMode::ClampToEdge,
+      WrappingMode::MirroredRepeat => WrapMode::MirroredRepeat,
+      WrappingMode::Repeat => WrapMode::Repeat,
+    }),
+    ..Default::default()
+  }
+}
+
+#[cfg(test)]
+mod scene_tests {
+  use cgmath::Vector4;
+
+  use super::*;
+  use crate::renderer::golden::headless_backend;
+
+  // A single unindexed triangle in the xy plane, with an embedded buffer.
+  const TRIANGLE: &str = r#"{
+    "asset": { "version": "2.0" },
+    "buffers": [{
+      "byteLength": 36,
+      "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+    }],
+    "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+    "accessors": [{
+      "bufferView": 0,
+      "componentType": 5126,
+      "count": 3,
+      "type": "VEC3",
+      "min": [0, 0, 0],
+      "max": [1, 1, 0]
+    }],
+    "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+    "nodes": [{ "mesh": 0 }],
+    "scenes": [{ "nodes": [0] }]
+  }"#;
+
+  #[test]
+  fn test_get_mesh() {
+    let (document, buffers, _) = gltf::import_slice(TRIANGLE.as_bytes()).unwrap();
+    let primitive = document
+      .meshes()
+      .next()
+      .unwrap()
+      .primitives()
+      .next()
+      .unwrap();
+
+    let mesh = get_mesh(&primitive, &buffers, 0).unwrap();
+    assert!(mesh.indices.is_none());
+    assert_eq!(mesh.vertices.len(), 3);
+    assert_eq!(mesh.vertices[1].position, [1.0, 0.0, 0.0]);
+    assert!(mesh
+      .vertices
+      .iter()
+      .all(|vertex| vertex.normal == [0.0, 0.0, 1.0] && vertex.color == [1.0, 1.0, 1.0, 1.0]));
+  }
+
+  #[test]
+  fn test_get_image() {
+    let data = gltf_image::Data {
+      pixels: vec![10, 20, 30, 40, 50, 60],
+      format: Format::R8G8B8,
+      width: 2,
+      height: 1,
+    };
+
+    let image = get_image(&data).unwrap().to_rgba8();
+    assert_eq!(image.get_pixel(1, 0).0, [40, 50, 60, 255]);
+  }
+
+  #[test]
+  fn test_failed_import() {
+    let Some(mut renderer) = headless_backend() else {
+      return;
+    };
+    let mut entities = EntityRegistry::new();
+
+    // Two nodes share the mesh, which also has a point primitive.
+    let gltf = TRIANGLE
+      .replace(
+        r#"{ "attributes": { "POSITION": 0 } }"#,
+        r#"{ "attributes": { "POSITION": 0 } }, { "attributes": { "POSITION": 0 }, "mode": 0 }"#,
+      )
+      .replace(
+        r#""nodes": [{ "mesh": 0 }]"#,
+        r#""nodes": [{ "mesh": 0 }, { "mesh": 0 }]"#,
+      )
+      .replace(
+        r#""scenes": [{ "nodes": [0] }]"#,
+        r#""scenes": [{ "nodes": [0, 1] }]"#,
+      );
+    let path = std::env::temp_dir().join("isle_scene_failed_import.gltf");
+    std::fs::write(&path, gltf).unwrap();
+
+    // The second node's actor is taken, so the import fails after the first.
+    let taken = renderer
+      .create_actor(Some(String::from("broken/node#1.0")))
+      .unwrap();
+    assert!(import_gltf(
+      &path,
+      Some(String::from("broken")),
+      &mut entities,
+      &mut renderer
+    )
+    .is_err());
+    assert!(!renderer.has_actor(&String::from("broken/node#0.0")));
+    assert!(renderer.has_actor(&taken));
+    assert!(entities
+      .get_component::<Transform>(&String::from("broken/node#0"))
+      .is_none());
+
+    renderer.remove_actor(taken).unwrap();
+    let scene = import_gltf(
+      &path,
+      Some(String::from("broken")),
+      &mut entities,
+      &mut renderer,
+    )
+    .unwrap();
+    assert_eq!(scene.entities.len(), 2);
+    assert_eq!(scene.skipped, vec!["broken/node#0.1", "broken/node#1.1"]);
+
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_sync_transforms() {
+    let Some(mut renderer) = headless_backend() else {
+      return;
+    };
+    let mut entities = EntityRegistry::new();
+    let parent = String::from("parent");
+    let child = String::from("child");
+
+    let actor = renderer.create_actor(None).unwrap();
+    renderer
+      .set_actor_translation(actor.clone(), [0.0, 0.0, 1.0])
+      .unwrap();
+
+    let moved = |translation| Transform {
+      translation,
+      ..Default::default()
+    };
+    entities.add_component(parent.clone(), moved([1.0, 0.0, 0.0]));
+    entities.add_component(child.clone(), moved([0.0, 2.0, 0.0]));
+    entities.add_component(
+      child.clone(),
+      MeshRenderer {
+        actors: vec![actor.clone()],
+      },
+    );
+    entities.set_parent(child.clone(), Some(parent.clone()));
+
+    sync_transforms(&entities, &mut renderer).unwrap();
+    let origin = renderer.actor_model_matrix(&actor).unwrap() * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    assert_eq!(origin, Vector4::new(1.0, 2.0, 1.0, 1.0));
+
+    // Moving the entity moves the actor on the next sync.
+    entities
+      .get_component_mut::<Transform>(&parent)
+      .unwrap()
+      .translation = [3.0, 0.0, 0.0];
+    sync_transforms(&entities, &mut renderer).unwrap();
+    let origin = renderer.actor_model_matrix(&actor).unwrap() * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    assert_eq!(origin, Vector4::new(3.0, 2.0, 1.0, 1.0));
+  }
+}
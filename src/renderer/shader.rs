@@ -0,0 +1,201 @@
+use std::{
+  collections::{HashMap, HashSet},
+  error::Error,
+  fmt, fs, io,
+  path::{Path, PathBuf},
+  sync::{
+    mpsc::{self, Receiver},
+    Arc,
+  },
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use shaderc::{Compiler, ShaderKind};
+use vulkano::{
+  device::Device,
+  shader::{ShaderCreationError, ShaderModule},
+};
+
+#[derive(Debug)]
+pub enum ShaderError {
+  Io(PathBuf, io::Error),
+  UnknownStage(PathBuf),
+  Compile(PathBuf, String),
+  Module(PathBuf, ShaderCreationError),
+  Watch(notify::Error),
+}
+
+impl fmt::Display for ShaderError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ShaderError::Io(path, e) => write!(f, "couldn't read {:?}: {}", path, e),
+      ShaderError::UnknownStage(path) => write!(f, "{:?} isn't a .vert, .frag or .spv file", path),
+      ShaderError::Compile(path, message) => {
+        write!(f, "couldn't compile {:?}: {}", path, message)
+      }
+      ShaderError::Module(path, e) => write!(f, "invalid SPIR-V in {:?}: {}", path, e),
+      ShaderError::Watch(e) => write!(f, "couldn't watch shader files: {}", e),
+    }
+  }
+}
+
+impl Error for ShaderError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      ShaderError::Io(_, e) => Some(e),
+      ShaderError::Module(_, e) => Some(e),
+      ShaderError::Watch(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+// `.spv` files are loaded as they are, `.vert` and `.frag` files are compiled
+// from GLSL. The module's interface is only checked once a pipeline uses it.
+pub fn load_shader(
+  device: Arc<Device>,
+  path: impl AsRef<Path>,
+) -> Result<Arc<ShaderModule>, ShaderError> {
+  let path = path.as_ref();
+
+  let module = match path.extension().and_then(|extension| extension.to_str()) {
+    Some("spv") => {
+      let bytes = fs::read(path).map_err(|e| ShaderError::Io(path.to_path_buf(), e))?;
+      unsafe { ShaderModule::from_bytes(device, &bytes) }
+    }
+    _ => {
+      let source = fs::read_to_string(path).map_err(|e| ShaderError::Io(path.to_path_buf(), e))?;
+      let words = compile_glsl(&source, path)?;
+      unsafe { ShaderModule::from_words(device, &words) }
+    }
+  };
+
+  module.map_err(|e| ShaderError::Module(path.to_path_buf(), e))
+}
+
+fn compile_glsl(source: &str, path: &Path) -> Result<Vec<u32>, ShaderError> {
+  let Some(kind) = get_shader_kind(path) else {
+    return Err(ShaderError::UnknownStage(path.to_path_buf()));
+  };
+
+  let Some(compiler) = Compiler::new() else {
+    return Err(ShaderError::Compile(
+      path.to_path_buf(),
+      String::from("shaderc is unavailable"),
+    ));
+  };
+
+  compiler
+    .compile_into_spirv(source, kind, &path.display().to_string(), "main", None)
+    .map(|artifact| artifact.as_binary().to_vec())
+    .map_err(|e| ShaderError::Compile(path.to_path_buf(), e.to_string()))
+}
+
+// Watches the directories holding shader files rather than the files, so
+// editors that save by replacing the file are still noticed.
+pub struct ShaderWatcher {
+  watcher: RecommendedWatcher,
+  events: Receiver<notify::Result<Event>>,
+  directories: HashSet<PathBuf>,
+  files: HashMap<PathBuf, HashSet<String>>,
+}
+
+impl ShaderWatcher {
+  pub fn new() -> Result<Self, ShaderError> {
+    let (sender, events) = mpsc::channel();
+
+    Ok(Self {
+      watcher: notify::recommended_watcher(sender).map_err(ShaderError::Watch)?,
+      events,
+      directories: HashSet::new(),
+      files: HashMap::new(),
+    })
+  }
+
+  pub fn watch(&mut self, shader: &str, path: impl AsRef<Path>) -> Result<(), ShaderError> {
+    let path = path.as_ref();
+    let path = fs::canonicalize(path).map_err(|e| ShaderError::Io(path.to_path_buf(), e))?;
+
+    if let Some(directory) = path.parent() {
+      if !self.directories.contains(directory) {
+        self
+          .watcher
+          .watch(directory, RecursiveMode::NonRecursive)
+          .map_err(ShaderError::Watch)?;
+        self.directories.insert(directory.to_path_buf());
+      }
+    }
+
+    self
+      .files
+      .entry(path)
+      .or_default()
+      .insert(String::from(shader));
+    Ok(())
+  }
+
+  pub fn unwatch(&mut self, shader: &str) {
+    for shaders in self.files.values_mut() {
+      shaders.remove(shader);
+    }
+    self.files.retain(|_, shaders| !shaders.is_empty());
+  }
+
+  // Returns the shaders with a file that changed since the last poll.
+  pub fn poll(&mut self) -> HashSet<String> {
+    let mut changed = HashSet::new();
+
+    for event in self.events.try_iter() {
+      match event {
+        Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+          for path in event.paths {
+            if let Some(shaders) = self.files.get(&path) {
+              changed.extend(shaders.iter().cloned());
+            }
+          }
+        }
+        Ok(_) => (),
+        Err(e) => println!("Failed to watch shader files: {:?}", e),
+      }
+    }
+
+    changed
+  }
+}
+
+fn get_shader_kind(path: &Path) -> Option<ShaderKind> {
+  match path.extension()?.to_str()? {
+    "vert" => Some(ShaderKind::Vertex),
+    "frag" => Some(ShaderKind::Fragment),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod shader_tests {
+  use super::*;
+
+  #[test]
+  fn test_shader_kind() {
+    assert_eq!(
+      get_shader_kind(Path::new("shaders/default.vert")),
+      Some(ShaderKind::Vertex)
+    );
+    assert_eq!(
+      get_shader_kind(Path::new("shaders/default.frag")),
+      Some(ShaderKind::Fragment)
+    );
+    assert_eq!(get_shader_kind(Path::new("shaders/default.glsl")), None);
+  }
+
+  #[test]
+  fn test_compile_glsl() {
+    let path = Path::new("shaders/default.frag");
+    let words = compile_glsl(&fs::read_to_string(path).unwrap(), path).unwrap();
+    assert_eq!(words[0], 0x07230203);
+
+    let error = compile_glsl("#version 450\nvoid main() { oops }", path).unwrap_err();
+    assert!(matches!(error, ShaderError::Compile(..)));
+    assert!(error.to_string().contains("default.frag:2"));
+  }
+}
@@ -0,0 +1,268 @@
+use std::{error::Error, sync::Arc};
+
+use vulkano::{
+  descriptor_set::WriteDescriptorSet,
+  device::Device,
+  format::Format,
+  image::view::ImageViewAbstract,
+  pipeline::{
+    graphics::{
+      depth_stencil::{CompareOp, DepthStencilState},
+      input_assembly::InputAssemblyState,
+      rasterization::{DepthBiasState, RasterizationState},
+      vertex_input::BuffersDefinition,
+      viewport::ViewportState,
+    },
+    GraphicsPipeline, StateMode,
+  },
+  render_pass::Subpass,
+  sampler::{BorderColor, Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
+};
+
+use super::{
+  graph::{AttachmentDesc, AttachmentSize, GraphError, PassDesc, RenderGraph},
+  vulkan::{InstanceData, Vertex},
+};
+
+// Size of the shadow map array in shaders/default.frag, which samples each map
+// in its own case. Shadow-casting lights past it are drawn unshadowed.
+pub const MAX_SHADOWS: usize = 4;
+
+const SHADOW_FORMAT: Format = Format::D16_UNORM;
+
+pub(super) mod shadow_vs {
+  vulkano_shaders::shader! {
+    ty: "vertex",
+    path: "shaders/shadow.vert",
+    types_meta: {
+      use bytemuck::{Pod, Zeroable};
+
+      #[derive(Clone, Copy, Zeroable, Pod)]
+    },
+  }
+}
+
+mod shadow_fs {
+  vulkano_shaders::shader! {
+    ty: "fragment",
+    path: "shaders/shadow.frag",
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowOptions {
+  // Width and height of every shadow map, in texels.
+  pub resolution: u32,
+  // Constant and slope-scaled depth offsets used while drawing the maps.
+  pub depth_bias: f32,
+  pub slope_bias: f32,
+  // World space offset along the surface normal before sampling a map.
+  pub normal_bias: f32,
+  // Each lookup averages (2 * radius + 1)^2 filtered comparisons.
+  pub pcf_radius: u32,
+}
+
+impl Default for ShadowOptions {
+  fn default() -> Self {
+    Self {
+      resolution: 2048,
+      depth_bias: 1.25,
+      slope_bias: 1.75,
+      normal_bias: 0.02,
+      pcf_radius: 1,
+    }
+  }
+}
+
+// The maps themselves are render graph attachments, drawn by one depth-only
+// pass per shadow slot. The biases are applied while recording, so only a new
+// resolution reallocates anything.
+pub(super) struct ShadowMaps {
+  options: ShadowOptions,
+  pipeline: Arc<GraphicsPipeline>,
+  sampler: Arc<Sampler>,
+}
+
+impl ShadowMaps {
+  pub(super) fn new(
+    device: Arc<Device>,
+    options: ShadowOptions,
+    graph: &RenderGraph,
+  ) -> Result<Self, Box<dyn Error>> {
+    let subpass = graph
+      .subpass(&get_shadow_pass(0))
+      .ok_or("the render graph has no shadow passes")?;
+    let pipeline = get_shadow_pipeline(device.clone(), subpass)?;
+    let sampler = Sampler::new(
+      device,
+      SamplerCreateInfo {
+        mag_filter: Filter::Linear,
+        min_filter: Filter::Linear,
+        address_mode: [SamplerAddressMode::ClampToBorder; 3],
+        // Anything outside a map is lit.
+        border_color: BorderColor::FloatOpaqueWhite,
+        compare: Some(CompareOp::LessOrEqual),
+        ..Default::default()
+      },
+    )?;
+
+    Ok(Self {
+      options,
+      pipeline,
+      sampler,
+    })
+  }
+
+  pub(super) fn options(&self) -> ShadowOptions {
+    self.options
+  }
+
+  pub(super) fn set_options(
+    &mut self,
+    graph: &mut RenderGraph,
+    options: ShadowOptions,
+  ) -> Result<(), Box<dyn Error>> {
+    if options.resolution == 0 {
+      return Err("shadow maps need a resolution above 0".into());
+    }
+
+    if options.resolution != self.options.resolution {
+      let size = AttachmentSize::Absolute([options.resolution; 2]);
+      for slot in 0..MAX_SHADOWS {
+        if let Err(e) = graph.set_attachment_size(&get_shadow_map(slot), size) {
+          // The failed map keeps its new size too, so it's put back with the
+          // ones before it.
+          let previous = AttachmentSize::Absolute([self.options.resolution; 2]);
+          for resized in 0..=slot {
+            graph.set_attachment_size(&get_shadow_map(resized), previous)?;
+          }
+          return Err(e.into());
+        }
+      }
+    }
+
+    self.options = options;
+    Ok(())
+  }
+
+  pub(super) fn pipeline(&self) -> &Arc<GraphicsPipeline> {
+    &self.pipeline
+  }
+
+  pub(super) fn descriptor_write(&self, binding: u32, graph: &RenderGraph) -> WriteDescriptorSet {
+    WriteDescriptorSet::image_view_sampler_array(
+      binding,
+      0,
+      (0..MAX_SHADOWS).filter_map(|slot| {
+        Some((
+          graph.attachment(&get_shadow_map(slot))?.clone() as Arc<dyn ImageViewAbstract>,
+          self.sampler.clone(),
+        ))
+      }),
+    )
+  }
+}
+
+// Declares a map and a pass drawing it for every shadow slot, and returns the
+// maps for the passes sampling them. See `set_active_shadows` for which of them
+// run each frame.
+pub(super) fn add_shadow_passes(
+  graph: &mut RenderGraph,
+  resolution: u32,
+) -> Result<Vec<String>, GraphError> {
+  let mut maps = Vec::new();
+  for slot in 0..MAX_SHADOWS {
+    let map = get_shadow_map(slot);
+    graph.add_attachment(
+      map.clone(),
+      AttachmentDesc::new(SHADOW_FORMAT)
+        .with_size(AttachmentSize::Absolute([resolution; 2]))
+        .with_clear(1.0),
+    )?;
+    graph.add_pass(
+      PassDesc::new(get_shadow_pass(slot)).depth(map.clone()),
+      None,
+    )?;
+    maps.push(map);
+  }
+
+  Ok(maps)
+}
+
+// Only the first `casters` maps are drawn. The rest aren't sampled, so their
+// passes are skipped instead of clearing every map each frame.
+pub(super) fn set_active_shadows(
+  graph: &mut RenderGraph,
+  casters: usize,
+) -> Result<(), GraphError> {
+  for slot in 0..MAX_SHADOWS {
+    graph.set_pass_enabled(&get_shadow_pass(slot), slot < casters)?;
+  }
+
+  Ok(())
+}
+
+// The shadow slot drawn by a pass, if it's one of the built-in shadow passes.
+pub(super) fn get_shadow_slot(pass: &str) -> Option<usize> {
+  (0..MAX_SHADOWS).find(|&slot| get_shadow_pass(slot) == pass)
+}
+
+fn get_shadow_pass(slot: usize) -> String {
+  format!("shadow{}", slot)
+}
+
+fn get_shadow_map(slot: usize) -> String {
+  format!("shadow_map{}", slot)
+}
+
+// Nothing is culled, so single-sided geometry casts shadows from both sides.
+fn get_shadow_pipeline(
+  device: Arc<Device>,
+  subpass: Subpass,
+) -> Result<Arc<GraphicsPipeline>, Box<dyn Error>> {
+  let vs = shadow_vs::load(device.clone())?;
+  let fs = shadow_fs::load(device.clone())?;
+  let (Some(vs), Some(fs)) = (vs.entry_point("main"), fs.entry_point("main")) else {
+    return Err("shadow shaders need a main entry point".into());
+  };
+
+  Ok(
+    GraphicsPipeline::start()
+      .vertex_input_state(
+        BuffersDefinition::new()
+          .vertex::<Vertex>()
+          .instance::<InstanceData>(),
+      )
+      .vertex_shader(vs, ())
+      .input_assembly_state(InputAssemblyState::new())
+      .rasterization_state(RasterizationState {
+        depth_bias: Some(DepthBiasState {
+          enable_dynamic: false,
+          bias: StateMode::Dynamic,
+        }),
+        ..RasterizationState::new()
+      })
+      .depth_stencil_state(DepthStencilState::simple_depth_test())
+      .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+      .fragment_shader(fs, ())
+      .render_pass(subpass)
+      .build(device)?,
+  )
+}
+
+#[cfg(test)]
+mod shadow_tests {
+  use super::*;
+
+  #[test]
+  fn test_shadow_slot() {
+    assert_eq!(get_shadow_slot("shadow0"), Some(0));
+    assert_eq!(
+      get_shadow_slot(&get_shadow_pass(MAX_SHADOWS - 1)),
+      Some(MAX_SHADOWS - 1)
+    );
+    assert_eq!(get_shadow_slot(&get_shadow_pass(MAX_SHADOWS)), None);
+    assert_eq!(get_shadow_slot("shadow01"), None);
+    assert_eq!(get_shadow_slot("shadow_map0"), None);
+  }
+}
@@ -0,0 +1,399 @@
+use std::{error::Error, sync::Arc};
+
+use image::{imageops, DynamicImage, Rgba, Rgba32FImage, RgbaImage};
+use vulkano::{
+  buffer::{BufferUsage, CpuAccessibleBuffer},
+  command_buffer::{BlitImageInfo, BufferImageCopy, CopyBufferToImageInfo, ImageBlit},
+  device::Device,
+  format::Format,
+  image::{
+    view::ImageView, ImageAccess, ImageCreateFlags, ImageDimensions, ImageLayout,
+    ImageSubresourceLayers, ImageUsage, ImmutableImage,
+  },
+  sampler::{
+    self, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode, LOD_CLAMP_NONE,
+  },
+};
+
+use super::upload::UploadBatch;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+  Nearest,
+  Linear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+  Repeat,
+  MirroredRepeat,
+  ClampToEdge,
+  ClampToBorder,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerOptions {
+  pub mag_filter: Filter,
+  pub min_filter: Filter,
+  // How neighbouring mip levels are blended; Linear gives trilinear filtering.
+  pub mipmap_filter: Filter,
+  // Clamped to what the device supports, and ignored if it has no anisotropic
+  // filtering.
+  pub anisotropy: Option<f32>,
+  // Wrap modes along u and v.
+  pub wrap: [WrapMode; 2],
+}
+
+impl Default for SamplerOptions {
+  fn default() -> Self {
+    Self {
+      mag_filter: Filter::Linear,
+      min_filter: Filter::Linear,
+      mipmap_filter: Filter::Linear,
+      anisotropy: None,
+      wrap: [WrapMode::Repeat, WrapMode::Repeat],
+    }
+  }
+}
+
+impl SamplerOptions {
+  // Keeps texels sharp, for pixel art and lookup tables.
+  pub fn nearest() -> Self {
+    Self {
+      mag_filter: Filter::Nearest,
+      min_filter: Filter::Nearest,
+      mipmap_filter: Filter::Nearest,
+      ..Default::default()
+    }
+  }
+
+  pub fn trilinear() -> Self {
+    Self::default()
+  }
+
+  pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+    self.anisotropy = Some(anisotropy);
+    self
+  }
+
+  pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+    self.wrap = [wrap, wrap];
+    self
+  }
+
+  // `max_anisotropy` is None when the device can't filter anisotropically.
+  fn create_info(&self, max_anisotropy: Option<f32>) -> SamplerCreateInfo {
+    let [u, v] = self.wrap.map(get_address_mode);
+    let anisotropy = match (self.anisotropy, max_anisotropy) {
+      (Some(anisotropy), Some(max)) if anisotropy > 1.0 => Some(anisotropy.min(max)),
+      _ => None,
+    };
+
+    SamplerCreateInfo {
+      mag_filter: get_filter(self.mag_filter),
+      min_filter: get_filter(self.min_filter),
+      mipmap_mode: match self.mipmap_filter {
+        Filter::Nearest => SamplerMipmapMode::Nearest,
+        Filter::Linear => SamplerMipmapMode::Linear,
+      },
+      address_mode: [u, v, SamplerAddressMode::ClampToEdge],
+      anisotropy,
+      lod: 0.0..=LOD_CLAMP_NONE,
+      ..Default::default()
+    }
+  }
+}
+
+pub struct Texture {
+  pub(super) view: Arc<ImageView<ImmutableImage>>,
+  pub(super) sampler: Arc<Sampler>,
+  dimensions: [u32; 2],
+  mip_levels: u32,
+}
+
+const TEXTURE_FORMAT: Format = Format::R8G8B8A8_SRGB;
+
+impl Texture {
+  // Accepts anything the image crate decodes; pixels are converted to 8-bit
+  // sRGB RGBA and get a full mip chain before upload.
+  pub fn from_image(
+    uploads: &mut UploadBatch,
+    image: &DynamicImage,
+    options: SamplerOptions,
+  ) -> Result<Self, Box<dyn Error>> {
+    Self::from_rgba(uploads, image.to_rgba8(), options)
+  }
+
+  pub fn white(uploads: &mut UploadBatch) -> Result<Self, Box<dyn Error>> {
+    Self::from_rgba(
+      uploads,
+      RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255])),
+      SamplerOptions::nearest(),
+    )
+  }
+
+  fn from_rgba(
+    uploads: &mut UploadBatch,
+    image: RgbaImage,
+    options: SamplerOptions,
+  ) -> Result<Self, Box<dyn Error>> {
+    let dimensions = [image.width(), image.height()];
+    if dimensions[0] == 0 || dimensions[1] == 0 {
+      return Err("can't upload an empty texture".into());
+    }
+
+    let device = uploads.device().clone();
+    let mip_levels = get_mip_levels(dimensions);
+    let (texture, init) = ImmutableImage::uninitialized(
+      device.clone(),
+      ImageDimensions::Dim2d {
+        width: dimensions[0],
+        height: dimensions[1],
+        array_layers: 1,
+      },
+      TEXTURE_FORMAT,
+      mip_levels,
+      ImageUsage {
+        transfer_src: true,
+        transfer_dst: true,
+        sampled: true,
+        ..Default::default()
+      },
+      ImageCreateFlags::empty(),
+      ImageLayout::ShaderReadOnlyOptimal,
+      [uploads.queue().queue_family_index()],
+    )?;
+
+    // Blitting needs linear filtering support on the format; otherwise every
+    // level is downsampled on the CPU and uploaded alongside the base level.
+    let features = device
+      .physical_device()
+      .format_properties(TEXTURE_FORMAT)
+      .optimal_tiling_features;
+    let levels =
+      match features.blit_src && features.blit_dst && features.sampled_image_filter_linear {
+        true => vec![image],
+        false => get_mip_chain(image),
+      };
+
+    let base = init.subresource_layers();
+    let mut pixels = Vec::new();
+    let mut regions = Vec::new();
+    for (level, image) in levels.iter().enumerate() {
+      regions.push(BufferImageCopy {
+        buffer_offset: pixels.len() as u64,
+        image_subresource: ImageSubresourceLayers {
+          mip_level: level as u32,
+          ..base.clone()
+        },
+        image_extent: [image.width(), image.height(), 1],
+        ..Default::default()
+      });
+      pixels.extend_from_slice(image.as_raw());
+    }
+
+    let staging = CpuAccessibleBuffer::from_iter(
+      device.clone(),
+      BufferUsage {
+        transfer_src: true,
+        ..Default::default()
+      },
+      false,
+      pixels,
+    )?;
+
+    let builder = uploads.builder()?;
+    builder.copy_buffer_to_image(CopyBufferToImageInfo {
+      regions: regions.into(),
+      ..CopyBufferToImageInfo::buffer_image(staging, init.clone())
+    })?;
+
+    for level in levels.len() as u32..mip_levels {
+      let [src_width, src_height] = get_mip_dimensions(dimensions, level - 1);
+      let [dst_width, dst_height] = get_mip_dimensions(dimensions, level);
+
+      builder.blit_image(BlitImageInfo {
+        regions: vec![ImageBlit {
+          src_subresource: ImageSubresourceLayers {
+            mip_level: level - 1,
+            ..base.clone()
+          },
+          src_offsets: [[0, 0, 0], [src_width, src_height, 1]],
+          dst_subresource: ImageSubresourceLayers {
+            mip_level: level,
+            ..base.clone()
+          },
+          dst_offsets: [[0, 0, 0], [dst_width, dst_height, 1]],
+          ..Default::default()
+        }]
+        .into(),
+        filter: sampler::Filter::Linear,
+        ..BlitImageInfo::images(init.clone(), init.clone())
+      })?;
+    }
+
+    Ok(Self {
+      view: ImageView::new_default(texture)?,
+      sampler: get_sampler(device, options)?,
+      dimensions,
+      mip_levels,
+    })
+  }
+
+  pub fn dimensions(&self) -> [u32; 2] {
+    self.dimensions
+  }
+
+  pub fn mip_levels(&self) -> u32 {
+    self.mip_levels
+  }
+}
+
+fn get_sampler(
+  device: Arc<Device>,
+  options: SamplerOptions,
+) -> Result<Arc<Sampler>, Box<dyn Error>> {
+  let max_anisotropy = match device.enabled_features().sampler_anisotropy {
+    true => Some(device.physical_device().properties().max_sampler_anisotropy),
+    false => None,
+  };
+
+  Ok(Sampler::new(device, options.create_info(max_anisotropy))?)
+}
+
+fn get_mip_levels([width, height]: [u32; 2]) -> u32 {
+  32 - width.max(height).max(1).leading_zeros()
+}
+
+fn get_mip_dimensions([width, height]: [u32; 2], level: u32) -> [u32; 2] {
+  [(width >> level).max(1), (height >> level).max(1)]
+}
+
+// Texels are sRGB-encoded, so levels are averaged in linear space like a blit
+// from an *_SRGB image would. Averaging the encoded bytes darkens every level.
+fn get_mip_chain(image: RgbaImage) -> Vec<RgbaImage> {
+  let dimensions = [image.width(), image.height()];
+  let mut linear = get_linear_image(&image);
+  let mut levels = vec![image];
+
+  for level in 1..get_mip_levels(dimensions) {
+    let [width, height] = get_mip_dimensions(dimensions, level);
+    linear = imageops::resize(&linear, width, height, imageops::FilterType::Triangle);
+    levels.push(get_srgb_image(&linear));
+  }
+
+  levels
+}
+
+fn get_linear_image(image: &RgbaImage) -> Rgba32FImage {
+  Rgba32FImage::from_fn(image.width(), image.height(), |x, y| {
+    let [r, g, b, a] = image.get_pixel(x, y).0;
+    Rgba([
+      srgb_to_linear(r),
+      srgb_to_linear(g),
+      srgb_to_linear(b),
+      a as f32 / 255.0,
+    ])
+  })
+}
+
+fn get_srgb_image(image: &Rgba32FImage) -> RgbaImage {
+  RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+    let [r, g, b, a] = image.get_pixel(x, y).0;
+    Rgba([
+      linear_to_srgb(r),
+      linear_to_srgb(g),
+      linear_to_srgb(b),
+      (a.clamp(0.0, 1.0) * 255.0).round() as u8,
+    ])
+  })
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+  let value = value as f32 / 255.0;
+  match value <= 0.04045 {
+    true => value / 12.92,
+    false => ((value + 0.055) / 1.055).powf(2.4),
+  }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+  let value = value.clamp(0.0, 1.0);
+  let value = match value <= 0.0031308 {
+    true => value * 12.92,
+    false => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+  };
+  (value * 255.0).round() as u8
+}
+
+fn get_filter(filter: Filter) -> sampler::Filter {
+  match filter {
+    Filter::Nearest => sampler::Filter::Nearest,
+    Filter::Linear => sampler::Filter::Linear,
+  }
+}
+
+fn get_address_mode(wrap: WrapMode) -> SamplerAddressMode {
+  match wrap {
+    WrapMode::Repeat => SamplerAddressMode::Repeat,
+    WrapMode::MirroredRepeat => SamplerAddressMode::MirroredRepeat,
+    WrapMode::ClampToEdge => SamplerAddressMode::ClampToEdge,
+    WrapMode::ClampToBorder => SamplerAddressMode::ClampToBorder,
+  }
+}
+
+#[cfg(test)]
+mod texture_tests {
+  use super::*;
+
+  #[test]
+  fn test_sampler_create_info() {
+    let info = SamplerOptions::nearest()
+      .with_wrap(WrapMode::ClampToBorder)
+      .create_info(None);
+
+    assert_eq!(info.mag_filter, sampler::Filter::Nearest);
+    assert_eq!(info.min_filter, sampler::Filter::Nearest);
+    assert_eq!(info.address_mode[0], SamplerAddressMode::ClampToBorder);
+    assert_eq!(info.address_mode[1], SamplerAddressMode::ClampToBorder);
+
+    let info = SamplerOptions::default().create_info(None);
+    assert_eq!(info.mag_filter, sampler::Filter::Linear);
+    assert_eq!(info.mipmap_mode, SamplerMipmapMode::Linear);
+    assert_eq!(info.address_mode[0], SamplerAddressMode::Repeat);
+  }
+
+  #[test]
+  fn test_anisotropy() {
+    let options = SamplerOptions::trilinear().with_anisotropy(16.0);
+    assert_eq!(options.create_info(Some(8.0)).anisotropy, Some(8.0));
+    assert_eq!(options.create_info(None).anisotropy, None);
+    assert_eq!(
+      SamplerOptions::default().create_info(Some(8.0)).anisotropy,
+      None
+    );
+  }
+
+  #[test]
+  fn test_mip_chain() {
+    assert_eq!(get_mip_levels([1, 1]), 1);
+    assert_eq!(get_mip_levels([256, 256]), 9);
+    assert_eq!(get_mip_levels([300, 17]), 9);
+    assert_eq!(get_mip_dimensions([300, 17], 5), [9, 1]);
+
+    let image = RgbaImage::from_pixel(8, 2, image::Rgba([200, 100, 50, 255]));
+    let levels = get_mip_chain(image);
+    let dimensions: Vec<_> = levels.iter().map(|level| level.dimensions()).collect();
+    assert_eq!(dimensions, vec![(8, 2), (4, 1), (2, 1), (1, 1)]);
+    assert_eq!(*levels[3].get_pixel(0, 0), image::Rgba([200, 100, 50, 255]));
+
+    // Half black, half white averages to half the light, not half the code value.
+    let mut image = RgbaImage::from_pixel(2, 1, image::Rgba([0, 0, 0, 255]));
+    image.put_pixel(1, 0, image::Rgba([255, 255, 255, 255]));
+    let levels = get_mip_chain(image);
+    assert_eq!(
+      *levels[1].get_pixel(0, 0),
+      image::Rgba([188, 188, 188, 255])
+    );
+  }
+}
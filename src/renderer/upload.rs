@@ -0,0 +1,147 @@
+use std::{error::Error, sync::Arc};
+
+use bytemuck::Pod;
+use vulkano::{
+  buffer::{BufferUsage, CpuAccessibleBuffer, DeviceLocalBuffer, TypedBufferAccess},
+  command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, PrimaryAutoCommandBuffer,
+    PrimaryCommandBuffer,
+  },
+  device::{Device, Queue},
+  sync::GpuFuture,
+};
+
+// Records staging copies into a single transfer command buffer, so loading a
+// scene costs one submission instead of one per mesh.
+pub struct UploadBatch {
+  device: Arc<Device>,
+  queue: Arc<Queue>,
+  builder: Option<AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>>,
+}
+
+impl UploadBatch {
+  pub fn new(device: Arc<Device>, queue: Arc<Queue>) -> Self {
+    Self {
+      device,
+      queue,
+      builder: None,
+    }
+  }
+
+  pub fn device(&self) -> &Arc<Device> {
+    &self.device
+  }
+
+  pub fn queue(&self) -> &Arc<Queue> {
+    &self.queue
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.builder.is_none()
+  }
+
+  pub fn builder(
+    &mut self,
+  ) -> Result<&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, Box<dyn Error>> {
+    if self.builder.is_none() {
+      self.builder = Some(AutoCommandBufferBuilder::primary(
+        self.device.clone(),
+        self.queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+      )?);
+    }
+
+    Ok(self.builder.as_mut().unwrap())
+  }
+
+  pub fn upload<T, I>(
+    &mut self,
+    data: I,
+    usage: BufferUsage,
+  ) -> Result<Arc<DeviceLocalBuffer<[T]>>, Box<dyn Error>>
+  where
+    T: Pod + Send + Sync,
+    I: IntoIterator<Item = T>,
+    I::IntoIter: ExactSizeIterator,
+  {
+    let data = data.into_iter();
+    let len = data.len() as u64;
+    let staging = self.stage(data)?;
+
+    let buffer = DeviceLocalBuffer::<[T]>::array(
+      self.device.clone(),
+      len,
+      BufferUsage {
+        transfer_dst: true,
+        ..usage
+      },
+      [self.queue.queue_family_index()],
+    )?;
+
+    self
+      .builder()?
+      .copy_buffer(CopyBufferInfo::buffers(staging, buffer.clone()))?;
+
+    Ok(buffer)
+  }
+
+  // Overwrites the start of an existing buffer, so meshes that keep or shrink
+  // their size don't need a new allocation.
+  pub fn upload_into<T, I>(
+    &mut self,
+    buffer: Arc<DeviceLocalBuffer<[T]>>,
+    data: I,
+  ) -> Result<(), Box<dyn Error>>
+  where
+    T: Pod + Send + Sync,
+    I: IntoIterator<Item = T>,
+    I::IntoIter: ExactSizeIterator,
+  {
+    let data = data.into_iter();
+    if data.len() as u64 > buffer.len() {
+      return Err("data doesn't fit in the destination buffer".into());
+    }
+
+    let staging = self.stage(data)?;
+    self
+      .builder()?
+      .copy_buffer(CopyBufferInfo::buffers(staging, buffer))?;
+
+    Ok(())
+  }
+
+  fn stage<T, I>(&self, data: I) -> Result<Arc<CpuAccessibleBuffer<[T]>>, Box<dyn Error>>
+  where
+    T: Pod + Send + Sync,
+    I: ExactSizeIterator<Item = T>,
+  {
+    if data.len() == 0 {
+      return Err("can't upload an empty buffer".into());
+    }
+
+    Ok(CpuAccessibleBuffer::from_iter(
+      self.device.clone(),
+      BufferUsage {
+        transfer_src: true,
+        ..Default::default()
+      },
+      false,
+      data,
+    )?)
+  }
+
+  pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+    let builder = match self.builder.take() {
+      Some(builder) => builder,
+      None => return Ok(()),
+    };
+
+    builder
+      .build()?
+      .execute(self.queue.clone())?
+      .then_signal_fence_and_flush()?
+      .wait(None)?;
+
+    Ok(())
+  }
+}
@@ -1,18 +1,35 @@
-use std::{collections::HashMap, error::Error, sync::Arc};
+#[cfg(feature = "shader-reload")]
+use std::path::PathBuf;
+use std::{
+  collections::{BTreeMap, HashMap},
+  error::Error,
+  path::Path,
+  sync::Arc,
+};
 
 use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3};
+use image::{DynamicImage, RgbaImage};
 use rand::{thread_rng, Rng};
 use vulkano::{
-  buffer::{BufferUsage, CpuAccessibleBuffer, TypedBufferAccess},
+  buffer::{
+    BufferAccess, BufferUsage, CpuAccessibleBuffer, CpuBufferPool, DeviceLocalBuffer,
+    TypedBufferAccess,
+  },
   command_buffer::{
-    AutoCommandBufferBuilder, CommandBufferExecFuture, CommandBufferUsage,
-    PrimaryAutoCommandBuffer, RenderPassBeginInfo, SubpassContents,
+    AutoCommandBufferBuilder, CommandBufferExecFuture, CommandBufferUsage, CopyImageToBufferInfo,
+    PrimaryAutoCommandBuffer, PrimaryCommandBuffer,
+  },
+  descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet},
+  device::{Device, DeviceCreateInfo, DeviceExtensions, Features, Queue, QueueCreateInfo},
+  format::Format,
+  image::{
+    view::{ImageView, ImageViewAbstract},
+    AttachmentImage, ImageAccess, ImageUsage, SwapchainImage,
   },
-  device::{Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo},
-  image::{view::ImageView, ImageUsage, SwapchainImage},
   instance::{
     debug::{DebugUtilsMessenger, DebugUtilsMessengerCreateInfo},
-    Instance, InstanceCreateInfo,
+    Instance, InstanceCreateInfo, InstanceExtensions,
   },
   pipeline::{
     graphics::{
@@ -20,9 +37,9 @@ use vulkano::{
       vertex_input::BuffersDefinition,
       viewport::{Viewport, ViewportState},
     },
-    GraphicsPipeline,
+    GraphicsPipeline, Pipeline, PipelineBindPoint,
   },
-  render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass},
+  render_pass::{RenderPass, Subpass},
   shader::ShaderModule,
   swapchain::{
     self, AcquireError, PresentFuture, PresentInfo, Surface, Swapchain, SwapchainAcquireFuture,
@@ -39,81 +56,152 @@ use winit::{
   window::{Window, WindowBuilder},
 };
 
+#[cfg(feature = "shader-reload")]
+use super::shader::{load_shader, ShaderWatcher};
+use super::{
+  camera::Camera,
+  error::RendererError,
+  graph::{AttachmentDesc, GraphError, PassDesc, RecordPass, RenderGraph, TARGET},
+  light::{LightData, MAX_LIGHTS},
+  material::{get_draw_order, Material, ShaderPair, DEFAULT_MATERIAL, DEFAULT_SHADER},
+  mesh::{Indices, Mesh},
+  obj::load_obj,
+  post::{PostOptions, PostStack, HDR, HDR_FORMAT},
+  shadow::{
+    add_shadow_passes, get_shadow_slot, set_active_shadows, shadow_vs, ShadowMaps, ShadowOptions,
+    MAX_SHADOWS,
+  },
+  texture::{SamplerOptions, Texture},
+  upload::UploadBatch,
+};
+
+// The built-in shaders are compiled in. With the `shader-reload` feature, the
+// same files can be loaded with `load_shader` to edit them while the renderer
+// runs.
 mod vs {
   vulkano_shaders::shader! {
     ty: "vertex",
-    src: "
-    #version 450
-    layout(location = 0) in vec3 position;
-    layout(location = 1) in vec4 color;
-
-    layout(location = 0) out vec4 out_color;
-
-    // layout(set = 0, binding = 0) uniform MVP {
-    //   mat4 model;
-    //   mat4 view;
-    //   mat4 proj;
-    // } mvp;
+    path: "shaders/default.vert",
+    types_meta: {
+      use bytemuck::{Pod, Zeroable};
 
-    void main() {
-      // gl_Position = mvp.proj * mvp.view * mvp.model * vec4(position, 1.0);
-      gl_Position = vec4(position, 1.0);
-      out_color = color;
-    }
-    "
+      #[derive(Clone, Copy, Zeroable, Pod)]
+    },
   }
 }
 
 mod fs {
   vulkano_shaders::shader! {
     ty: "fragment",
-    src: "
-    #version 450
-    layout(location = 0) in vec4 in_color;
+    path: "shaders/default.frag",
+    types_meta: {
+      use bytemuck::{Pod, Zeroable};
 
-    layout(location = 0) out vec4 f_color;
-
-    void main() {
-      f_color = in_color;
-    }
-    "
+      #[derive(Clone, Copy, Zeroable, Pod)]
+    },
   }
 }
 
 #[repr(C)]
-#[derive(Default, Copy, Clone, Zeroable, Pod)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Zeroable, Pod)]
 pub struct Vertex {
   pub position: [f32; 3],
   pub color: [f32; 4],
+  pub uv: [f32; 2],
+  pub normal: [f32; 3],
+}
+vulkano::impl_vertex!(Vertex, position, color, uv, normal);
+
+#[repr(C)]
+#[derive(Copy, Clone, Zeroable, Pod)]
+pub struct InstanceData {
+  instance_model_0: [f32; 4],
+  instance_model_1: [f32; 4],
+  instance_model_2: [f32; 4],
+  instance_model_3: [f32; 4],
+  instance_color: [f32; 4],
+}
+vulkano::impl_vertex!(
+  InstanceData,
+  instance_model_0,
+  instance_model_1,
+  instance_model_2,
+  instance_model_3,
+  instance_color
+);
+
+impl InstanceData {
+  pub fn new(model: [[f32; 4]; 4], color: [f32; 4]) -> Self {
+    Self {
+      instance_model_0: model[0],
+      instance_model_1: model[1],
+      instance_model_2: model[2],
+      instance_model_3: model[3],
+      instance_color: color,
+    }
+  }
+
+  pub fn from_transform(
+    translation: [f32; 3],
+    rotation: [f32; 4],
+    scale: [f32; 3],
+    color: [f32; 4],
+  ) -> Self {
+    Self::new(get_model_matrix(translation, rotation, scale).into(), color)
+  }
+}
+
+impl Default for InstanceData {
+  fn default() -> Self {
+    Self::new(Matrix4::identity().into(), [1.0, 1.0, 1.0, 1.0])
+  }
+}
+
+enum IndexBuffer {
+  U16(Arc<DeviceLocalBuffer<[u16]>>),
+  U32(Arc<DeviceLocalBuffer<[u32]>>),
 }
-vulkano::impl_vertex!(Vertex, position, color);
 
 struct Actor {
   name: String,
-  buffer: Option<Arc<CpuAccessibleBuffer<[Vertex]>>>,
-  tri_count: u32,
+  buffer: Option<Arc<DeviceLocalBuffer<[Vertex]>>>,
+  index_buffer: Option<IndexBuffer>,
+  instances: Option<Arc<CpuAccessibleBuffer<[InstanceData]>>>,
+  // Number of indices, or vertices for non-indexed meshes, to draw. Can be
+  // smaller than the buffers after `update_model` reuses them.
+  draw_count: u32,
+  // False while the actor's buffers wait on copies in `uploads`, and after a
+  // failed flush until a later upload succeeds, so unfilled buffers aren't drawn.
+  uploaded: bool,
+  visible: bool,
+  texture: Option<String>,
+  texture_set: Arc<PersistentDescriptorSet>,
+  material: String,
+  // Placement of the entity drawing this actor, applied on top of the actor's
+  // own transform. See `scene::sync_transforms`.
+  world: Matrix4<f32>,
   translation: [f32; 3],
   scale: [f32; 3],
   rotation: [f32; 4],
 }
 
-pub struct VulkanBackend {
-  _debug: Option<DebugUtilsMessenger>,
+impl Actor {
+  fn model_matrix(&self) -> Matrix4<f32> {
+    self.world * get_model_matrix(self.translation, self.rotation, self.scale)
+  }
+}
+
+struct WindowTarget {
   surface: Arc<Surface<Window>>,
-  device: Arc<Device>,
-  queue: Arc<Queue>,
   event_loop: EventLoop<()>,
-  render_pass: Arc<RenderPass>,
-  framebuffers: Vec<Arc<Framebuffer>>,
   swapchain: Arc<Swapchain<Window>>,
   swapchain_images: Vec<Arc<SwapchainImage<Window>>>,
-  vertex_shader: Arc<ShaderModule>,
-  fragment_shader: Arc<ShaderModule>,
-  viewport: Viewport,
-  actors: HashMap<String, Actor>,
-  pipeline: Arc<GraphicsPipeline>,
+  views: Vec<Arc<ImageView<SwapchainImage<Window>>>>,
   window_resized: bool,
   recreate_swapchain: bool,
+  capture_enabled: bool,
+  // The copy of the last presented frame: its image index, extent and pixels.
+  capture: Option<(usize, [u32; 2], Arc<CpuAccessibleBuffer<[u8]>>)>,
   fences: Vec<
     Option<
       Arc<
@@ -132,65 +220,74 @@ pub struct VulkanBackend {
   previous_fence_i: usize,
 }
 
-impl VulkanBackend {
-  pub fn new() -> Result<Self, Box<dyn Error>> {
-    let library = VulkanLibrary::new()?;
-    let required_extensions = vulkano_win::required_extensions(&library);
-    let instance = Instance::new(
-      library,
-      InstanceCreateInfo {
-        enabled_extensions: required_extensions,
-        enabled_layers: vec!["VK_LAYER_KHRONOS_validation".to_string()],
-        ..Default::default()
-      },
-    )?;
+struct OffscreenTarget {
+  image: Arc<AttachmentImage>,
+  view: Arc<ImageView<AttachmentImage>>,
+}
 
-    let _debug = unsafe {
-      DebugUtilsMessenger::new(
-        instance.clone(),
-        DebugUtilsMessengerCreateInfo::user_callback(Arc::new(|msg| {
-          println!("Vulkan: {:?}", msg.description);
-        })),
-      )
-      .ok()
-    };
+enum RenderTarget {
+  Window(WindowTarget),
+  Offscreen(OffscreenTarget),
+}
 
-    let physical = match instance.enumerate_physical_devices()?.next() {
-      Some(physical) => physical,
-      None => return Err("no device available".into()),
-    };
+pub struct VulkanBackend {
+  _debug: Option<DebugUtilsMessenger>,
+  device: Arc<Device>,
+  queue: Arc<Queue>,
+  graph: RenderGraph,
+  // The scene pass's render pass, which material pipelines are built for. The
+  // graph recreates it whenever it's recompiled, but always compatibly.
+  render_pass: Arc<RenderPass>,
+  viewport: Viewport,
+  actors: HashMap<String, Actor>,
+  shaders: HashMap<String, ShaderPair>,
+  // Vertex and fragment files of the shaders loaded with `load_shader`.
+  #[cfg(feature = "shader-reload")]
+  shader_files: HashMap<String, [PathBuf; 2]>,
+  #[cfg(feature = "shader-reload")]
+  shader_watcher: Option<ShaderWatcher>,
+  materials: HashMap<String, Material>,
+  pipelines: HashMap<Material, Arc<GraphicsPipeline>>,
+  // The default material's pipeline; descriptor sets are allocated from its
+  // layouts and bound to every other pipeline.
+  pipeline: Arc<GraphicsPipeline>,
+  camera_buffers: CpuBufferPool<vs::ty::Camera>,
+  cameras: Vec<Camera>,
+  light_buffers: CpuBufferPool<fs::ty::Lights>,
+  lights: Vec<LightData>,
+  // White by default, so scenes without lights look unlit.
+  ambient_light: [f32; 3],
+  shadow_maps: ShadowMaps,
+  post: PostStack,
+  uploads: UploadBatch,
+  // Actors with copies recorded into `uploads` since the last flush.
+  pending_uploads: Vec<String>,
+  textures: HashMap<String, Texture>,
+  default_texture_set: Arc<PersistentDescriptorSet>,
+  target: RenderTarget,
+}
 
-    let queue_family_index = match physical
-      .queue_family_properties()
-      .iter()
-      .enumerate()
-      .position(|(_, q)| q.queue_flags.graphics)
-    {
-      Some(index) => index,
-      None => return Err("couldn't find a graphical queue family".into()),
-    } as u32;
+impl VulkanBackend {
+  pub fn new() -> Result<Self, RendererError> {
+    Ok(Self::create_windowed()?)
+  }
+
+  pub fn new_headless(width: u32, height: u32) -> Result<Self, RendererError> {
+    Ok(Self::create_headless(width, height)?)
+  }
+
+  fn create_windowed() -> Result<Self, Box<dyn Error>> {
+    let library = VulkanLibrary::new()?;
+    let required_extensions = vulkano_win::required_extensions(&library);
+    let instance = get_instance(library, required_extensions)?;
+    let _debug = get_debug_messenger(&instance);
 
     let device_extensions = DeviceExtensions {
       khr_swapchain: true,
       ..DeviceExtensions::empty()
     };
-
-    let (device, mut queues) = Device::new(
-      physical.clone(),
-      DeviceCreateInfo {
-        queue_create_infos: vec![QueueCreateInfo {
-          queue_family_index,
-          ..Default::default()
-        }],
-        enabled_extensions: device_extensions,
-        ..Default::default()
-      },
-    )?;
-
-    let queue = match queues.next() {
-      Some(queue) => queue,
-      None => return Err("no queue available".into()),
-    };
+    let (device, queue) = get_device(&instance, device_extensions)?;
+    let physical = device.physical_device().clone();
 
     let event_loop = EventLoop::new();
     let surface = WindowBuilder::new().build_vk_surface(&event_loop, instance.clone())?;
@@ -202,7 +299,7 @@ impl VulkanBackend {
       .supported_composite_alpha
       .iter()
       .next()
-      .unwrap();
+      .ok_or("the surface doesn't support any composite alpha mode")?;
     let image_format = Some(physical.surface_formats(&surface, Default::default())?[0].0);
 
     let (swapchain, swapchain_images) = Swapchain::new(
@@ -214,6 +311,7 @@ impl VulkanBackend {
         image_extent: dimensions.into(),
         image_usage: ImageUsage {
           color_attachment: true,
+          transfer_src: capabilities.supported_usage_flags.transfer_src,
           ..Default::default()
         },
         composite_alpha,
@@ -221,311 +319,1654 @@ impl VulkanBackend {
       },
     )?;
 
-    let render_pass = get_render_pass(device.clone(), &swapchain);
-    let framebuffers = get_framebuffers(&swapchain_images, &render_pass);
+    let views = get_target_views(&swapchain_images)?;
+    let frames_in_flight = swapchain_images.len();
+    let target = RenderTarget::Window(WindowTarget {
+      surface,
+      event_loop,
+      swapchain,
+      swapchain_images,
+      views,
+      window_resized: false,
+      recreate_swapchain: false,
+      capture_enabled: false,
+      capture: None,
+      fences: vec![None; frames_in_flight],
+      previous_fence_i: 0,
+    });
+
+    Self::with_target(_debug, device, queue, target)
+  }
+
+  fn create_headless(width: u32, height: u32) -> Result<Self, Box<dyn Error>> {
+    let library = VulkanLibrary::new()?;
+    let instance = get_instance(library, InstanceExtensions::empty())?;
+    let _debug = get_debug_messenger(&instance);
+
+    let (device, queue) = get_device(&instance, DeviceExtensions::empty())?;
+
+    let image = AttachmentImage::with_usage(
+      device.clone(),
+      [width, height],
+      OFFSCREEN_FORMAT,
+      ImageUsage {
+        color_attachment: true,
+        transfer_src: true,
+        ..Default::default()
+      },
+    )?;
+    let view = ImageView::new_default(image.clone())?;
+    let target = RenderTarget::Offscreen(OffscreenTarget { image, view });
+
+    Self::with_target(_debug, device, queue, target)
+  }
+
+  // Everything but the target is set up the same for windows and offscreen
+  // images.
+  fn with_target(
+    _debug: Option<DebugUtilsMessenger>,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    target: RenderTarget,
+  ) -> Result<Self, Box<dyn Error>> {
+    let (format, extent) = match &target {
+      RenderTarget::Window(target) => (
+        target.swapchain.image_format(),
+        target.swapchain.image_extent(),
+      ),
+      RenderTarget::Offscreen(target) => (
+        target.image.format(),
+        target.image.dimensions().width_height(),
+      ),
+    };
+
+    let mut graph = get_render_graph(device.clone(), format, extent)?;
+    let post = PostStack::new(device.clone(), &mut graph, PostOptions::default())?;
+    let render_pass = get_scene_render_pass(&graph)?;
 
-    let vertex_shader = vs::load(device.clone())?;
-    let fragment_shader = fs::load(device.clone())?;
+    let shaders = ShaderPair {
+      vertex: vs::load(device.clone())?,
+      fragment: fs::load(device.clone())?,
+    };
 
     let viewport = Viewport {
       origin: [0.0, 0.0],
-      dimensions: surface.window().inner_size().into(),
+      dimensions: extent.map(|dimension| dimension as f32),
       depth_range: 0.0..1.0,
     };
 
     let pipeline = get_pipeline(
       device.clone(),
-      vertex_shader.clone(),
-      fragment_shader.clone(),
+      &shaders,
+      &Material::default(),
       render_pass.clone(),
-      viewport.clone(),
-    );
+    )?;
+    let pipelines = HashMap::from([(Material::default(), pipeline.clone())]);
 
-    let frames_in_flight = swapchain_images.len();
+    let camera_buffers = CpuBufferPool::uniform_buffer(device.clone());
+    let light_buffers = CpuBufferPool::uniform_buffer(device.clone());
+    let shadow_maps = ShadowMaps::new(device.clone(), ShadowOptions::default(), &graph)?;
+    let mut uploads = UploadBatch::new(device.clone(), queue.clone());
+    let default_texture_set = get_texture_set(&pipeline, &Texture::white(&mut uploads)?)?;
 
     Ok(Self {
       _debug,
-      surface,
       device,
       queue,
-      event_loop,
+      graph,
       render_pass,
-      framebuffers,
-      swapchain,
-      swapchain_images,
-      vertex_shader,
-      fragment_shader,
       viewport,
       actors: HashMap::new(),
+      shaders: HashMap::from([(String::from(DEFAULT_SHADER), shaders)]),
+      #[cfg(feature = "shader-reload")]
+      shader_files: HashMap::new(),
+      #[cfg(feature = "shader-reload")]
+      shader_watcher: None,
+      materials: HashMap::from([(String::from(DEFAULT_MATERIAL), Material::default())]),
+      pipelines,
       pipeline,
-      window_resized: false,
-      recreate_swapchain: false,
-      fences: vec![None; frames_in_flight],
-      previous_fence_i: 0,
+      camera_buffers,
+      cameras: Vec::new(),
+      light_buffers,
+      lights: Vec::new(),
+      ambient_light: [1.0, 1.0, 1.0],
+      shadow_maps,
+      post,
+      uploads,
+      pending_uploads: Vec::new(),
+      textures: HashMap::new(),
+      default_texture_set,
+      target,
     })
   }
 
-  pub fn create_actor(&mut self, name: Option<String>) {
+  pub fn is_headless(&self) -> bool {
+    matches!(self.target, RenderTarget::Offscreen(_))
+  }
+
+  pub fn dimensions(&self) -> [u32; 2] {
+    match &self.target {
+      RenderTarget::Window(target) => target.swapchain.image_extent(),
+      RenderTarget::Offscreen(target) => target.image.dimensions().width_height(),
+    }
+  }
+
+  pub fn create_actor(&mut self, name: Option<String>) -> Result<String, RendererError> {
     let name = match name {
       Some(name) => name,
       None => gen_id(None),
     };
 
+    if self.actors.contains_key(&name) {
+      return Err(RendererError::DuplicateActor(name));
+    }
+
+    let instances = get_instance_buffer(self.device.clone(), vec![InstanceData::default()])?;
+
     self.actors.insert(
       name.clone(),
       Actor {
-        name,
+        name: name.clone(),
         buffer: None,
-        tri_count: 0,
+        index_buffer: None,
+        instances: Some(instances),
+        draw_count: 0,
+        uploaded: true,
+        visible: true,
+        texture: None,
+        texture_set: self.default_texture_set.clone(),
+        material: String::from(DEFAULT_MATERIAL),
+        world: Matrix4::identity(),
         translation: [0.0, 0.0, 0.0],
         scale: [1.0, 1.0, 1.0],
         rotation: [0.0, 0.0, 0.0, 1.0],
       },
     );
+
+    Ok(name)
   }
 
-  pub fn upload_model(&mut self, actor: String, model: Vec<Vertex>) {
-    let mut actor = self.actors.get_mut(&actor).unwrap();
+  // In-flight frames keep their own references to the actor's buffers, so they
+  // are only freed once the GPU is done with them.
+  pub fn remove_actor(&mut self, actor: String) -> Result<(), RendererError> {
+    match self.actors.remove(&actor) {
+      Some(_) => Ok(()),
+      None => Err(RendererError::UnknownActor(actor)),
+    }
+  }
 
-    actor.tri_count = (model.len() / 3) as u32;
+  pub fn has_actor(&self, actor: &String) -> bool {
+    self.actors.contains_key(actor)
+  }
 
-    let buffer = CpuAccessibleBuffer::from_iter(
-      self.device.clone(),
+  pub fn upload_model(&mut self, actor: String, model: Vec<Vertex>) -> Result<(), RendererError> {
+    self.upload_mesh(actor, Mesh::from_vertices(model))
+  }
+
+  pub fn load_model(&mut self, actor: String, path: impl AsRef<Path>) -> Result<(), RendererError> {
+    self.upload_mesh(actor, load_obj(path)?)
+  }
+
+  pub fn upload_mesh(&mut self, actor: String, mesh: Mesh) -> Result<(), RendererError> {
+    let actor = get_actor(&mut self.actors, actor)?;
+    if mesh.vertices.is_empty() {
+      return Err(RendererError::EmptyMesh(actor.name.clone()));
+    }
+
+    // Meshes are static, so they're staged into device-local memory. The copies
+    // are batched and submitted together before the next frame.
+    let buffer = self.uploads.upload(
+      mesh.vertices,
       BufferUsage {
         vertex_buffer: true,
         ..Default::default()
       },
-      false,
-      model.into_iter(),
-    )
-    .unwrap();
+    )?;
+
+    let index_usage = BufferUsage {
+      index_buffer: true,
+      ..Default::default()
+    };
+    let index_buffer = match mesh.indices {
+      Some(Indices::U16(indices)) => {
+        Some(IndexBuffer::U16(self.uploads.upload(indices, index_usage)?))
+      }
+      Some(Indices::U32(indices)) => {
+        Some(IndexBuffer::U32(self.uploads.upload(indices, index_usage)?))
+      }
+      None => None,
+    };
 
+    actor.draw_count = match &index_buffer {
+      Some(IndexBuffer::U16(indices)) => indices.len() as u32,
+      Some(IndexBuffer::U32(indices)) => indices.len() as u32,
+      None => buffer.len() as u32,
+    };
     actor.buffer = Some(buffer);
+    actor.index_buffer = index_buffer;
+    actor.uploaded = false;
+    self.pending_uploads.push(actor.name.clone());
+
+    Ok(())
   }
 
-  pub fn render(&mut self) -> bool {
-    let mut close_requested = false;
-    self
-      .event_loop
-      .run_return(|event, _, control_flow| match event {
-        Event::WindowEvent {
-          event: WindowEvent::CloseRequested,
-          ..
-        } => {
-          close_requested = true;
-          *control_flow = ControlFlow::Exit;
-        }
-        Event::WindowEvent {
-          event: WindowEvent::Resized(_),
-          ..
-        } => self.window_resized = true,
-        Event::RedrawEventsCleared => {
-          if self.window_resized || self.recreate_swapchain {
-            self.recreate_swapchain = false;
-
-            let dimensions = self.surface.window().inner_size();
-            let (swapchain, swapchain_images) = match self.swapchain.recreate(SwapchainCreateInfo {
-              image_extent: dimensions.into(),
-              ..self.swapchain.create_info()
-            }) {
-              Ok(r) => r,
-              Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
-              Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
-            };
+  // Writes into the actor's existing buffers when the new mesh fits and uses the
+  // same index width, and falls back to a fresh upload otherwise.
+  pub fn update_model(&mut self, actor: String, mesh: Mesh) -> Result<(), RendererError> {
+    let target = get_actor(&mut self.actors, actor.clone())?;
+    if mesh.vertices.is_empty() {
+      return Err(RendererError::EmptyMesh(actor));
+    }
 
-            self.swapchain = swapchain;
-            self.framebuffers = get_framebuffers(&swapchain_images, &self.render_pass);
-            self.swapchain_images = swapchain_images;
+    let buffer = match &target.buffer {
+      Some(buffer) if mesh_fits(buffer, &target.index_buffer, &mesh) => buffer.clone(),
+      _ => return self.upload_mesh(actor, mesh),
+    };
+    let vertex_count = mesh.vertices.len() as u32;
+    self.uploads.upload_into(buffer, mesh.vertices)?;
 
-            if self.window_resized {
-              self.window_resized = false;
+    target.draw_count = match (&target.index_buffer, mesh.indices) {
+      (Some(IndexBuffer::U16(buffer)), Some(Indices::U16(indices))) => {
+        let count = indices.len() as u32;
+        self.uploads.upload_into(buffer.clone(), indices)?;
+        count
+      }
+      (Some(IndexBuffer::U32(buffer)), Some(Indices::U32(indices))) => {
+        let count = indices.len() as u32;
+        self.uploads.upload_into(buffer.clone(), indices)?;
+        count
+      }
+      _ => vertex_count,
+    };
+    target.uploaded = false;
+    self.pending_uploads.push(actor);
 
-              self.viewport.dimensions = dimensions.into();
-              self.pipeline = get_pipeline(
-                self.device.clone(),
-                self.vertex_shader.clone(),
-                self.fragment_shader.clone(),
-                self.render_pass.clone(),
-                self.viewport.clone(),
-              );
-            }
-          }
+    Ok(())
+  }
 
-          let (image_i, suboptimal, acquire_future) =
-            match swapchain::acquire_next_image(self.swapchain.clone(), None) {
-              Ok(r) => r,
-              Err(AcquireError::OutOfDate) => {
-                self.recreate_swapchain = true;
-                return;
-              }
-              Err(e) => panic!("Failed to acquire next image: {:?}", e),
-            };
+  pub fn set_visible(&mut self, actor: String, visible: bool) -> Result<(), RendererError> {
+    get_actor(&mut self.actors, actor)?.visible = visible;
+    Ok(())
+  }
 
-          if suboptimal {
-            self.recreate_swapchain = true;
-          }
+  pub fn is_visible(&self, actor: &String) -> Option<bool> {
+    self.actors.get(actor).map(|actor| actor.visible)
+  }
 
-          if let Some(image_fence) = &self.fences[image_i] {
-            image_fence.wait(None).unwrap();
-          }
+  // Draws the actor's model once per instance, with each instance's transform
+  // applied on top of the actor's own. An empty list draws nothing.
+  pub fn set_actor_instances(
+    &mut self,
+    actor: String,
+    instances: Vec<InstanceData>,
+  ) -> Result<(), RendererError> {
+    let actor = get_actor(&mut self.actors, actor)?;
 
-          let previous_future = match self.fences[self.previous_fence_i].clone() {
-            None => {
-              let mut now = sync::now(self.device.clone());
-              now.cleanup_finished();
+    if instances.is_empty() {
+      actor.instances = None;
+      return Ok(());
+    }
 
-              now.boxed()
-            }
-            Some(fence) => fence.boxed(),
-          };
+    if let Some(buffer) = &actor.instances {
+      if buffer.len() == instances.len() as u64 {
+        if let Ok(mut content) = buffer.write() {
+          content.copy_from_slice(&instances);
+          return Ok(());
+        }
+      }
+    }
 
-          let command_buffer = get_command_buffers(
-            self.device.clone(),
-            self.queue.clone(),
-            self.pipeline.clone(),
-            self.framebuffers[image_i].clone(),
-            &self.actors.values().collect::<Vec<_>>(),
-          );
+    actor.instances = Some(get_instance_buffer(self.device.clone(), instances)?);
+    Ok(())
+  }
 
-          let future = previous_future
-            .join(acquire_future)
-            .then_execute(self.queue.clone(), command_buffer)
-            .unwrap()
-            .then_swapchain_present(
-              self.queue.clone(),
-              PresentInfo {
-                index: image_i,
-                ..PresentInfo::swapchain(self.swapchain.clone())
-              },
-            )
-            .then_signal_fence_and_flush();
+  pub fn clear_actor_instances(&mut self, actor: String) -> Result<(), RendererError> {
+    self.set_actor_instances(actor, vec![InstanceData::default()])
+  }
 
-          self.fences[image_i] = match future {
-            Ok(value) => Some(Arc::new(value)),
-            Err(FlushError::OutOfDate) => {
-              self.recreate_swapchain = true;
-              None
-            }
-            Err(e) => {
-              println!("Failed to flush future: {:?}", e);
-              None
-            }
-          };
+  pub fn set_actor_transform(
+    &mut self,
+    actor: String,
+    translation: [f32; 3],
+    rotation: [f32; 4],
+    scale: [f32; 3],
+  ) -> Result<(), RendererError> {
+    let actor = get_actor(&mut self.actors, actor)?;
 
-          self.previous_fence_i = image_i;
+    actor.translation = translation;
+    actor.rotation = rotation;
+    actor.scale = scale;
+    Ok(())
+  }
 
-          *control_flow = ControlFlow::Exit;
-        }
-        _ => (),
-      });
+  pub fn set_actor_translation(
+    &mut self,
+    actor: String,
+    translation: [f32; 3],
+  ) -> Result<(), RendererError> {
+    get_actor(&mut self.actors, actor)?.translation = translation;
+    Ok(())
+  }
 
-    return close_requested;
+  pub fn set_actor_rotation(
+    &mut self,
+    actor: String,
+    rotation: [f32; 4],
+  ) -> Result<(), RendererError> {
+    get_actor(&mut self.actors, actor)?.rotation = rotation;
+    Ok(())
   }
-}
 
-fn get_render_pass(device: Arc<Device>, swapchain: &Arc<Swapchain<Window>>) -> Arc<RenderPass> {
-  vulkano::single_pass_renderpass!(
-    device,
-    attachments: {
-      color: {
-        load: Clear,
-        store: Store,
-        format: swapchain.image_format(),
-        samples: 1,
-      }
-    },
-    pass: {
-      color: [color],
-      depth_stencil: {}
-    }
-  )
-  .unwrap()
-}
+  pub fn set_actor_scale(&mut self, actor: String, scale: [f32; 3]) -> Result<(), RendererError> {
+    get_actor(&mut self.actors, actor)?.scale = scale;
+    Ok(())
+  }
 
-fn get_framebuffers(
-  images: &[Arc<SwapchainImage<Window>>],
-  render_pass: &Arc<RenderPass>,
-) -> Vec<Arc<Framebuffer>> {
-  images
-    .iter()
-    .map(|image| {
-      let view = ImageView::new_default(image.clone()).unwrap();
-      Framebuffer::new(
-        render_pass.clone(),
-        FramebufferCreateInfo {
-          attachments: vec![view],
-          ..Default::default()
-        },
-      )
-      .unwrap()
-    })
-    .collect::<Vec<_>>()
-}
+  pub fn set_actor_world_matrix(
+    &mut self,
+    actor: String,
+    world: Matrix4<f32>,
+  ) -> Result<(), RendererError> {
+    get_actor(&mut self.actors, actor)?.world = world;
+    Ok(())
+  }
 
-fn get_pipeline(
-  device: Arc<Device>,
-  vs: Arc<ShaderModule>,
-  fs: Arc<ShaderModule>,
-  render_pass: Arc<RenderPass>,
-  viewport: Viewport,
-) -> Arc<GraphicsPipeline> {
-  GraphicsPipeline::start()
-    .vertex_input_state(BuffersDefinition::new().vertex::<Vertex>())
-    .vertex_shader(vs.entry_point("main").unwrap(), ())
-    .input_assembly_state(InputAssemblyState::new())
-    .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
-    .fragment_shader(fs.entry_point("main").unwrap(), ())
-    .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
-    .build(device.clone())
-    .unwrap()
-}
+  pub fn actor_model_matrix(&self, actor: &String) -> Option<Matrix4<f32>> {
+    self.actors.get(actor).map(|actor| actor.model_matrix())
+  }
 
-fn get_command_buffers(
-  device: Arc<Device>,
-  queue: Arc<Queue>,
-  pipeline: Arc<GraphicsPipeline>,
-  framebuffer: Arc<Framebuffer>,
-  actors: &Vec<&Actor>,
-) -> Arc<PrimaryAutoCommandBuffer> {
-  let mut builder = AutoCommandBufferBuilder::primary(
-    device,
-    queue.queue_family_index(),
-    CommandBufferUsage::MultipleSubmit,
-  )
-  .unwrap();
+  pub fn create_texture(
+    &mut self,
+    name: Option<String>,
+    image: &DynamicImage,
+    options: SamplerOptions,
+  ) -> Result<String, RendererError> {
+    let name = match name {
+      Some(name) => name,
+      None => gen_id(None),
+    };
 
-  builder
-    .begin_render_pass(
-      RenderPassBeginInfo {
-        clear_values: vec![Some([0.1, 0.1, 0.1, 1.0].into())],
-        ..RenderPassBeginInfo::framebuffer(framebuffer)
-      },
-      SubpassContents::Inline,
-    )
-    .unwrap()
-    .bind_pipeline_graphics(pipeline);
-  // .bind_vertex_buffers(0, vertex_buffer.clone())
-  // .draw(vertex_buffer.len() as u32, 1, 0, 0)
+    if self.textures.contains_key(&name) {
+      return Err(RendererError::DuplicateTexture(name));
+    }
 
-  add_actor_buffers(&mut builder, actors);
+    let texture = Texture::from_image(&mut self.uploads, image, options)?;
+    self.textures.insert(name.clone(), texture);
 
-  builder.end_render_pass().unwrap();
+    Ok(name)
+  }
 
-  Arc::new(builder.build().unwrap())
-}
+  pub fn load_texture(
+    &mut self,
+    name: Option<String>,
+    path: impl AsRef<Path>,
+    options: SamplerOptions,
+  ) -> Result<String, RendererError> {
+    let image = image::open(path)?;
+    self.create_texture(name, &image, options)
+  }
+
+  // Actors that still use the texture keep drawing with it until they're
+  // given another one.
+  pub fn remove_texture(&mut self, texture: String) -> Result<(), RendererError> {
+    match self.textures.remove(&texture) {
+      Some(_) => Ok(()),
+      None => Err(RendererError::UnknownTexture(texture)),
+    }
+  }
+
+  pub fn texture_dimensions(&self, texture: &String) -> Option<[u32; 2]> {
+    self.textures.get(texture).map(Texture::dimensions)
+  }
+
+  // Passing None goes back to drawing the actor untextured.
+  pub fn set_actor_texture(
+    &mut self,
+    actor: String,
+    texture: Option<String>,
+  ) -> Result<(), RendererError> {
+    let texture_set = match &texture {
+      Some(name) => match self.textures.get(name) {
+        Some(texture) => get_texture_set(&self.pipeline, texture)?,
+        None => return Err(RendererError::UnknownTexture(name.clone())),
+      },
+      None => self.default_texture_set.clone(),
+    };
+
+    let actor = get_actor(&mut self.actors, actor)?;
+    actor.texture = texture;
+    actor.texture_set = texture_set;
+    Ok(())
+  }
+
+  pub fn actor_texture(&self, actor: &String) -> Option<&String> {
+    self.actors.get(actor)?.texture.as_ref()
+  }
+
+  // Replacing a shader rebuilds every pipeline that uses it. If any of them
+  // fails to build, the previous shader and pipelines stay in place.
+  pub fn register_shader(
+    &mut self,
+    name: String,
+    vertex: Arc<ShaderModule>,
+    fragment: Arc<ShaderModule>,
+  ) -> Result<(), RendererError> {
+    let shaders = ShaderPair { vertex, fragment };
+
+    let mut rebuilt = Vec::new();
+    for material in self
+      .pipelines
+      .keys()
+      .filter(|material| material.shader == name)
+    {
+      let pipeline = get_pipeline(
+        self.device.clone(),
+        &shaders,
+        material,
+        self.render_pass.clone(),
+      )?;
+      rebuilt.push((material.clone(), pipeline));
+    }
+
+    self.pipelines.extend(rebuilt);
+    self.shaders.insert(name, shaders);
+    self.pipeline = self.pipelines[&Material::default()].clone();
+    Ok(())
+  }
+
+  // Compiles or loads a shader pair from disk and registers it under `name`.
+  // The files are watched from then on, and saving either one rebuilds the
+  // pipelines using it on the next frame.
+  #[cfg(feature = "shader-reload")]
+  pub fn load_shader(
+    &mut self,
+    name: String,
+    vertex: impl AsRef<Path>,
+    fragment: impl AsRef<Path>,
+  ) -> Result<(), RendererError> {
+    let files = [vertex, fragment].map(|path| path.as_ref().to_path_buf());
+    let [vertex, fragment] = files
+      .clone()
+      .map(|path| load_shader(self.device.clone(), path));
+    self.register_shader(name.clone(), vertex?, fragment?)?;
+
+    let watcher = match &mut self.shader_watcher {
+      Some(watcher) => watcher,
+      None => self.shader_watcher.insert(ShaderWatcher::new()?),
+    };
+    watcher.unwatch(&name);
+    for path in &files {
+      watcher.watch(&name, path)?;
+    }
+
+    self.shader_files.insert(name, files);
+    Ok(())
+  }
+
+  // A shader that fails to compile or link keeps its last good pipelines.
+  #[cfg(feature = "shader-reload")]
+  fn reload_shaders(&mut self) {
+    let Some(watcher) = &mut self.shader_watcher else {
+      return;
+    };
+
+    for name in watcher.poll() {
+      let Some([vertex, fragment]) = self.shader_files.get(&name) else {
+        continue;
+      };
+
+      let result = match (
+        load_shader(self.device.clone(), vertex),
+        load_shader(self.device.clone(), fragment),
+      ) {
+        (Ok(vertex), Ok(fragment)) => self.register_shader(name.clone(), vertex, fragment),
+        (Err(e), _) | (_, Err(e)) => Err(e.into()),
+      };
+
+      match result {
+        Ok(()) => println!("Reloaded shader {:?}", name),
+        Err(e) => println!("Failed to reload shader {:?}: {}", name, e),
+      }
+    }
+  }
+
+  // Creates or replaces a named material. Pipelines are built here and shared
+  // by every material with the same shader and state.
+  pub fn create_material(&mut self, name: String, material: Material) -> Result<(), RendererError> {
+    if !self.pipelines.contains_key(&material) {
+      let shaders = match self.shaders.get(&material.shader) {
+        Some(shaders) => shaders,
+        None => return Err(RendererError::UnknownShader(material.shader)),
+      };
+
+      let pipeline = get_pipeline(
+        self.device.clone(),
+        shaders,
+        &material,
+        self.render_pass.clone(),
+      )?;
+      self.pipelines.insert(material.clone(), pipeline);
+    }
+
+    self.materials.insert(name, material);
+    Ok(())
+  }
+
+  // Actors still using the material are drawn with the default one. The
+  // default material itself can't be removed.
+  pub fn remove_material(&mut self, name: String) -> Result<(), RendererError> {
+    if name == DEFAULT_MATERIAL {
+      return Err(RendererError::UnknownMaterial(name));
+    }
+
+    match self.materials.remove(&name) {
+      Some(_) => Ok(()),
+      None => Err(RendererError::UnknownMaterial(name)),
+    }
+  }
+
+  pub fn material(&self, name: &String) -> Option<&Material> {
+    self.materials.get(name)
+  }
+
+  pub fn set_actor_material(
+    &mut self,
+    actor: String,
+    material: String,
+  ) -> Result<(), RendererError> {
+    if !self.materials.contains_key(&material) {
+      return Err(RendererError::UnknownMaterial(material));
+    }
+
+    get_actor(&mut self.actors, actor)?.material = material;
+    Ok(())
+  }
+
+  pub fn actor_material(&self, actor: &String) -> Option<&String> {
+    self.actors.get(actor).map(|actor| &actor.material)
+  }
+
+  pub fn set_camera(&mut self, camera: Camera) {
+    self.set_cameras(vec![camera]);
+  }
+
+  pub fn set_cameras(&mut self, cameras: Vec<Camera>) {
+    self.cameras = cameras;
+    update_camera_aspects(&mut self.cameras, &self.viewport);
+  }
+
+  pub fn add_camera(&mut self, camera: Camera) -> usize {
+    self.cameras.push(camera);
+    update_camera_aspects(&mut self.cameras, &self.viewport);
+    self.cameras.len() - 1
+  }
+
+  pub fn camera_mut(&mut self, index: usize) -> Option<&mut Camera> {
+    self.cameras.get_mut(index)
+  }
+
+  pub fn clear_cameras(&mut self) {
+    self.cameras.clear();
+  }
+
+  // Replaces the lights drawn from the next frame on. Only the first
+  // MAX_LIGHTS are used.
+  pub fn set_lights(&mut self, lights: Vec<LightData>) {
+    self.lights = lights;
+  }
+
+  pub fn lights(&self) -> &[LightData] {
+    &self.lights
+  }
+
+  pub fn set_ambient_light(&mut self, color: [f32; 3]) {
+    self.ambient_light = color;
+  }
+
+  pub fn set_shadow_options(&mut self, options: ShadowOptions) -> Result<(), RendererError> {
+    Ok(self.shadow_maps.set_options(&mut self.graph, options)?)
+  }
+
+  pub fn shadow_options(&self) -> ShadowOptions {
+    self.shadow_maps.options()
+  }
+
+  pub fn set_post_options(&mut self, options: PostOptions) -> Result<(), RendererError> {
+    Ok(
+      self
+        .post
+        .set_options(self.device.clone(), &mut self.graph, options)?,
+    )
+  }
+
+  pub fn post_options(&self) -> PostOptions {
+    self.post.options()
+  }
+
+  pub fn add_render_attachment(
+    &mut self,
+    name: String,
+    desc: AttachmentDesc,
+  ) -> Result<(), RendererError> {
+    self.graph.add_attachment(name, desc)?;
+    Ok(self.graph.compile()?)
+  }
+
+  // Extra passes are ordered among the built-in shadow and scene passes by the
+  // attachments they use. A pass that doesn't fit in the graph is taken out
+  // again.
+  pub fn add_render_pass(
+    &mut self,
+    pass: PassDesc,
+    record: RecordPass,
+  ) -> Result<(), RendererError> {
+    let name = pass.name.clone();
+    self.graph.add_pass(pass, Some(record))?;
+
+    if let Err(e) = self.graph.compile() {
+      self.graph.remove_pass(&name)?;
+      self.graph.compile()?;
+      return Err(e.into());
+    }
+
+    Ok(())
+  }
+
+  // Only passes added with `add_render_pass` can be removed.
+  pub fn remove_render_pass(&mut self, name: &str) -> Result<(), RendererError> {
+    if name == SCENE_PASS || get_shadow_slot(name).is_some() {
+      return Err(GraphError::UnknownPass(String::from(name)).into());
+    }
+
+    self.graph.remove_pass(name)?;
+    Ok(self.graph.compile()?)
+  }
+
+  // Actors whose copies were lost in a failed flush stay hidden until they're
+  // uploaded again.
+  pub fn flush_uploads(&mut self) -> Result<(), Box<dyn Error>> {
+    let result = self.uploads.flush();
+    let pending = std::mem::take(&mut self.pending_uploads);
+    result?;
+
+    for name in pending {
+      if let Some(actor) = self.actors.get_mut(&name) {
+        actor.uploaded = true;
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn render(&mut self) -> bool {
+    #[cfg(feature = "shader-reload")]
+    self.reload_shaders();
+
+    // `update_model` may overwrite buffers that earlier frames are still reading.
+    if !self.uploads.is_empty() {
+      if let RenderTarget::Window(target) = &self.target {
+        for fence in target.fences.iter().flatten() {
+          if let Err(e) = fence.wait(None) {
+            println!("Failed to wait for frame: {:?}", e);
+          }
+        }
+      }
+    }
+
+    if let Err(e) = self.flush_uploads() {
+      println!("Failed to upload buffers: {:?}", e);
+    }
+
+    if let RenderTarget::Offscreen(_) = self.target {
+      if let Err(e) = self.render_offscreen() {
+        println!("Failed to render offscreen frame: {:?}", e);
+      }
+      return false;
+    }
+
+    let (lights, shadow_casters) = match get_light_buffer(
+      &self.light_buffers,
+      &self.lights,
+      self.ambient_light,
+      &self.shadow_maps,
+    ) {
+      Ok(lights) => lights,
+      Err(e) => {
+        println!("Failed to upload lights: {}", e);
+        return false;
+      }
+    };
+
+    let RenderTarget::Window(target) = &mut self.target else {
+      return false;
+    };
+
+    let mut close_requested = false;
+    target
+      .event_loop
+      .run_return(|event, _, control_flow| match event {
+        Event::WindowEvent {
+          event: WindowEvent::CloseRequested,
+          ..
+        } => {
+          close_requested = true;
+          *control_flow = ControlFlow::Exit;
+        }
+        Event::WindowEvent {
+          event: WindowEvent::Resized(_),
+          ..
+        } => target.window_resized = true,
+        Event::RedrawEventsCleared => {
+          if target.window_resized || target.recreate_swapchain {
+            target.recreate_swapchain = false;
+
+            let dimensions = target.surface.window().inner_size();
+            let (swapchain, swapchain_images) =
+              match target.swapchain.recreate(SwapchainCreateInfo {
+                image_extent: dimensions.into(),
+                ..target.swapchain.create_info()
+              }) {
+                Ok(r) => r,
+                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
+                Err(e) => {
+                  println!("Failed to recreate swapchain: {:?}", e);
+                  *control_flow = ControlFlow::Exit;
+                  return;
+                }
+              };
+
+            target.swapchain = swapchain;
+            target.views = match get_target_views(&swapchain_images) {
+              Ok(views) => views,
+              Err(e) => {
+                println!("Failed to create swapchain image views: {}", e);
+                *control_flow = ControlFlow::Exit;
+                return;
+              }
+            };
+            target.swapchain_images = swapchain_images;
+
+            if let Err(e) = self.graph.resize(target.swapchain.image_extent()) {
+              println!("Failed to resize render graph: {}", e);
+              *control_flow = ControlFlow::Exit;
+              return;
+            }
+
+            if target.window_resized {
+              target.window_resized = false;
+
+              self.viewport.dimensions = dimensions.into();
+              update_camera_aspects(&mut self.cameras, &self.viewport);
+            }
+          }
+
+          let (image_i, suboptimal, acquire_future) =
+            match swapchain::acquire_next_image(target.swapchain.clone(), None) {
+              Ok(r) => r,
+              Err(AcquireError::OutOfDate) => {
+                target.recreate_swapchain = true;
+                return;
+              }
+              Err(e) => {
+                println!("Failed to acquire next image: {:?}", e);
+                *control_flow = ControlFlow::Exit;
+                return;
+              }
+            };
+
+          if suboptimal {
+            target.recreate_swapchain = true;
+          }
+
+          if let Some(image_fence) = &target.fences[image_i] {
+            if let Err(e) = image_fence.wait(None) {
+              println!("Failed to wait for frame: {:?}", e);
+              *control_flow = ControlFlow::Exit;
+              return;
+            }
+          }
+
+          let previous_future = match target.fences[target.previous_fence_i].clone() {
+            None => {
+              let mut now = sync::now(self.device.clone());
+              now.cleanup_finished();
+
+              now.boxed()
+            }
+            Some(fence) => fence.boxed(),
+          };
+
+          let capture = match target.capture_enabled {
+            true => {
+              match get_readback_buffer(self.device.clone(), target.swapchain.image_extent()) {
+                Ok(buffer) => Some(buffer),
+                Err(e) => {
+                  println!("Failed to create capture buffer: {:?}", e);
+                  None
+                }
+              }
+            }
+            false => None,
+          };
+
+          let camera_views = match get_camera_views(
+            &self.camera_buffers,
+            &self.pipeline,
+            &self.cameras,
+            &self.viewport,
+            lights.clone(),
+            &self.shadow_maps,
+            &self.graph,
+          ) {
+            Ok(camera_views) => camera_views,
+            Err(e) => {
+              println!("Failed to record frame: {}", e);
+              *control_flow = ControlFlow::Exit;
+              return;
+            }
+          };
+
+          let command_buffer = match get_command_buffers(
+            self.device.clone(),
+            self.queue.clone(),
+            &mut self.graph,
+            target.views[image_i].clone(),
+            camera_views,
+            &get_draw_batches(&self.actors, &self.materials, &self.pipelines),
+            &self.shadow_maps,
+            &shadow_casters,
+            &self.post,
+            capture.clone().map(|buffer| {
+              (
+                target.swapchain_images[image_i].clone() as Arc<dyn ImageAccess>,
+                buffer,
+              )
+            }),
+          ) {
+            Ok(command_buffer) => command_buffer,
+            Err(e) => {
+              println!("Failed to record frame: {}", e);
+              *control_flow = ControlFlow::Exit;
+              return;
+            }
+          };
+
+          let future = match previous_future
+            .join(acquire_future)
+            .then_execute(self.queue.clone(), command_buffer)
+          {
+            Ok(future) => future,
+            Err(e) => {
+              println!("Failed to submit frame: {:?}", e);
+              *control_flow = ControlFlow::Exit;
+              return;
+            }
+          };
+
+          let future = future
+            .then_swapchain_present(
+              self.queue.clone(),
+              PresentInfo {
+                index: image_i,
+                ..PresentInfo::swapchain(target.swapchain.clone())
+              },
+            )
+            .then_signal_fence_and_flush();
+
+          target.fences[image_i] = match future {
+            Ok(value) => {
+              if let Some(buffer) = capture {
+                target.capture = Some((image_i, target.swapchain.image_extent(), buffer));
+              }
+              Some(Arc::new(value))
+            }
+            Err(FlushError::OutOfDate) => {
+              target.recreate_swapchain = true;
+              None
+            }
+            Err(e) => {
+              println!("Failed to flush future: {:?}", e);
+              None
+            }
+          };
+
+          target.previous_fence_i = image_i;
+
+          *control_flow = ControlFlow::Exit;
+        }
+        _ => (),
+      });
+
+    return close_requested;
+  }
+
+  fn render_offscreen(&mut self) -> Result<(), Box<dyn Error>> {
+    let (lights, shadow_casters) = get_light_buffer(
+      &self.light_buffers,
+      &self.lights,
+      self.ambient_light,
+      &self.shadow_maps,
+    )?;
+    let camera_views = get_camera_views(
+      &self.camera_buffers,
+      &self.pipeline,
+      &self.cameras,
+      &self.viewport,
+      lights,
+      &self.shadow_maps,
+      &self.graph,
+    )?;
+
+    let RenderTarget::Offscreen(target) = &self.target else {
+      return Err("the backend has no offscreen target".into());
+    };
+
+    let command_buffer = get_command_buffers(
+      self.device.clone(),
+      self.queue.clone(),
+      &mut self.graph,
+      target.view.clone(),
+      camera_views,
+      &get_draw_batches(&self.actors, &self.materials, &self.pipelines),
+      &self.shadow_maps,
+      &shadow_casters,
+      &self.post,
+      None,
+    )?;
+
+    command_buffer
+      .execute(self.queue.clone())?
+      .then_signal_fence_and_flush()?
+      .wait(None)?;
+
+    Ok(())
+  }
+
+  // Swapchain images can only be copied while acquired, so while capture is
+  // enabled each window frame is copied before it's presented.
+  pub fn set_frame_capture(&mut self, enabled: bool) -> Result<(), Box<dyn Error>> {
+    let RenderTarget::Window(target) = &mut self.target else {
+      return Ok(());
+    };
+
+    if enabled && !target.swapchain.image_usage().transfer_src {
+      return Err("swapchain images don't support transfer_src".into());
+    }
+
+    target.capture_enabled = enabled;
+    if !enabled {
+      target.capture = None;
+    }
+
+    Ok(())
+  }
+
+  // Returns the last rendered frame without drawing a new one. Offscreen
+  // targets are read back directly; window targets need `set_frame_capture`.
+  pub fn capture_frame(&mut self) -> Result<RgbaImage, Box<dyn Error>> {
+    let target = match &mut self.target {
+      RenderTarget::Offscreen(target) => {
+        let image = target.image.clone();
+        let [width, height] = image.dimensions().width_height();
+        let buffer = get_readback_buffer(self.device.clone(), [width, height])?;
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+          self.device.clone(),
+          self.queue.queue_family_index(),
+          CommandBufferUsage::OneTimeSubmit,
+        )?;
+        builder.copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buffer.clone()))?;
+
+        builder
+          .build()?
+          .execute(self.queue.clone())?
+          .then_signal_fence_and_flush()?
+          .wait(None)?;
+
+        let pixels = buffer.read()?.to_vec();
+        return get_rgba_image(width, height, OFFSCREEN_FORMAT, pixels);
+      }
+      RenderTarget::Window(target) => target,
+    };
+
+    let (image_i, [width, height], buffer) = match &target.capture {
+      Some(capture) => capture.clone(),
+      None if !target.capture_enabled => return Err("frame capture isn't enabled".into()),
+      None => return Err("no frame was rendered".into()),
+    };
+
+    if let Some(fence) = &target.fences[image_i] {
+      fence.wait(None)?;
+    }
+
+    let pixels = buffer.read()?.to_vec();
+    get_rgba_image(width, height, target.swapchain.image_format(), pixels)
+  }
+
+  pub fn save_frame(&mut self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+    self.capture_frame()?.save(path)?;
+    Ok(())
+  }
+}
+
+const OFFSCREEN_FORMAT: Format = Format::R8G8B8A8_UNORM;
+const DEPTH_FORMAT: Format = Format::D16_UNORM;
+
+const SCENE_PASS: &str = "scene";
+const DEPTH: &str = "depth";
+
+fn get_instance(
+  library: Arc<VulkanLibrary>,
+  enabled_extensions: InstanceExtensions,
+) -> Result<Arc<Instance>, Box<dyn Error>> {
+  let validation = String::from("VK_LAYER_KHRONOS_validation");
+  let enabled_layers = match library
+    .layer_properties()?
+    .any(|layer| layer.name() == validation)
+  {
+    true => vec![validation],
+    false => vec![],
+  };
+
+  let instance = Instance::new(
+    library,
+    InstanceCreateInfo {
+      enabled_extensions,
+      enabled_layers,
+      ..Default::default()
+    },
+  )?;
+
+  Ok(instance)
+}
+
+fn get_debug_messenger(instance: &Arc<Instance>) -> Option<DebugUtilsMessenger> {
+  unsafe {
+    DebugUtilsMessenger::new(
+      instance.clone(),
+      DebugUtilsMessengerCreateInfo::user_callback(Arc::new(|msg| {
+        println!("Vulkan: {:?}", msg.description);
+      })),
+    )
+    .ok()
+  }
+}
+
+fn get_device(
+  instance: &Arc<Instance>,
+  device_extensions: DeviceExtensions,
+) -> Result<(Arc<Device>, Arc<Queue>), Box<dyn Error>> {
+  let physical = match instance.enumerate_physical_devices()?.next() {
+    Some(physical) => physical,
+    None => return Err("no device available".into()),
+  };
+
+  let queue_family_index = match physical
+    .queue_family_properties()
+    .iter()
+    .enumerate()
+    .position(|(_, q)| q.queue_flags.graphics)
+  {
+    Some(index) => index,
+    None => return Err("couldn't find a graphical queue family".into()),
+  } as u32;
+
+  let (device, mut queues) = Device::new(
+    physical,
+    DeviceCreateInfo {
+      queue_create_infos: vec![QueueCreateInfo {
+        queue_family_index,
+        ..Default::default()
+      }],
+      enabled_extensions: device_extensions,
+      enabled_features: Features {
+        sampler_anisotropy: physical.supported_features().sampler_anisotropy,
+        ..Features::empty()
+      },
+      ..Default::default()
+    },
+  )?;
+
+  let queue = match queues.next() {
+    Some(queue) => queue,
+    None => return Err("no queue available".into()),
+  };
+
+  Ok((device, queue))
+}
+
+fn get_readback_buffer(
+  device: Arc<Device>,
+  [width, height]: [u32; 2],
+) -> Result<Arc<CpuAccessibleBuffer<[u8]>>, Box<dyn Error>> {
+  let buffer = CpuAccessibleBuffer::from_iter(
+    device,
+    BufferUsage {
+      transfer_dst: true,
+      ..Default::default()
+    },
+    true,
+    (0..width * height * 4).map(|_| 0u8),
+  )?;
+
+  Ok(buffer)
+}
+
+fn get_rgba_image(
+  width: u32,
+  height: u32,
+  format: Format,
+  mut pixels: Vec<u8>,
+) -> Result<RgbaImage, Box<dyn Error>> {
+  match format {
+    Format::R8G8B8A8_UNORM | Format::R8G8B8A8_SRGB => {}
+    Format::B8G8R8A8_UNORM | Format::B8G8R8A8_SRGB => {
+      for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+      }
+    }
+    format => return Err(format!("can't capture frames in {:?}", format).into()),
+  }
+
+  match RgbaImage::from_raw(width, height, pixels) {
+    Some(image) => Ok(image),
+    None => Err("captured frame has the wrong size".into()),
+  }
+}
+
+// Shadow maps are drawn first, then the scene into the HDR target. The scene
+// pass samples every map, so it's always ordered after them. The graph is
+// compiled once the post stack has added its passes.
+fn get_render_graph(
+  device: Arc<Device>,
+  format: Format,
+  extent: [u32; 2],
+) -> Result<RenderGraph, GraphError> {
+  let mut graph = RenderGraph::new(device, extent);
+  graph.add_attachment(
+    TARGET,
+    AttachmentDesc::new(format).with_clear([0.0, 0.0, 0.0, 1.0]),
+  )?;
+  graph.add_attachment(
+    HDR,
+    AttachmentDesc::new(HDR_FORMAT).with_clear([0.1, 0.1, 0.1, 1.0]),
+  )?;
+  graph.add_attachment(DEPTH, AttachmentDesc::new(DEPTH_FORMAT).with_clear(1.0))?;
+
+  let mut scene = PassDesc::new(SCENE_PASS).color(HDR).depth(DEPTH);
+  for map in add_shadow_passes(&mut graph, ShadowOptions::default().resolution)? {
+    scene = scene.read(map);
+  }
+  graph.add_pass(scene, None)?;
+
+  Ok(graph)
+}
+
+fn get_scene_render_pass(graph: &RenderGraph) -> Result<Arc<RenderPass>, Box<dyn Error>> {
+  let subpass = graph
+    .subpass(SCENE_PASS)
+    .ok_or("the render graph has no scene pass")?;
+  Ok(subpass.render_pass().clone())
+}
+
+fn get_target_views<I>(images: &[Arc<I>]) -> Result<Vec<Arc<ImageView<I>>>, Box<dyn Error>>
+where
+  I: ImageAccess + 'static,
+{
+  let mut views = Vec::new();
+  for image in images {
+    views.push(ImageView::new_default(image.clone())?);
+  }
+  Ok(views)
+}
+
+fn get_pipeline(
+  device: Arc<Device>,
+  shaders: &ShaderPair,
+  material: &Material,
+  render_pass: Arc<RenderPass>,
+) -> Result<Arc<GraphicsPipeline>, Box<dyn Error>> {
+  let (Some(vs), Some(fs)) = (
+    shaders.vertex.entry_point("main"),
+    shaders.fragment.entry_point("main"),
+  ) else {
+    return Err("shaders need a main entry point".into());
+  };
+
+  Ok(
+    GraphicsPipeline::start()
+      .vertex_input_state(
+        BuffersDefinition::new()
+          .vertex::<Vertex>()
+          .instance::<InstanceData>(),
+      )
+      .vertex_shader(vs, ())
+      .input_assembly_state(InputAssemblyState::new())
+      .rasterization_state(material.rasterization_state())
+      .depth_stencil_state(material.depth_stencil_state())
+      .color_blend_state(material.color_blend_state())
+      .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+      .fragment_shader(fs, ())
+      .render_pass(Subpass::from(render_pass, 0).ok_or("render pass has no subpass")?)
+      .build(device)?,
+  )
+}
+
+// Groups visible, uploaded actors by pipeline, in the order their materials draw.
+fn get_draw_batches<'a>(
+  actors: &'a HashMap<String, Actor>,
+  materials: &HashMap<String, Material>,
+  pipelines: &HashMap<Material, Arc<GraphicsPipeline>>,
+) -> Vec<(Arc<GraphicsPipeline>, Vec<&'a Actor>)> {
+  let mut batches: BTreeMap<_, Vec<&Actor>> = BTreeMap::new();
+  for actor in actors
+    .values()
+    .filter(|actor| actor.visible && actor.uploaded)
+  {
+    let material = materials
+      .get(&actor.material)
+      .unwrap_or(&materials[DEFAULT_MATERIAL]);
+    batches
+      .entry(get_draw_order(material))
+      .or_default()
+      .push(actor);
+  }
+
+  batches
+    .into_iter()
+    .filter_map(|((_, material), actors)| Some((pipelines.get(material)?.clone(), actors)))
+    .collect()
+}
+
+fn get_texture_set(
+  pipeline: &Arc<GraphicsPipeline>,
+  texture: &Texture,
+) -> Result<Arc<PersistentDescriptorSet>, Box<dyn Error>> {
+  let layout = pipeline
+    .layout()
+    .set_layouts()
+    .get(1)
+    .ok_or("the pipeline has no texture set")?;
+  Ok(PersistentDescriptorSet::new(
+    layout.clone(),
+    [WriteDescriptorSet::image_view_sampler(
+      0,
+      texture.view.clone(),
+      texture.sampler.clone(),
+    )],
+  )?)
+}
+
+fn get_camera_set(
+  buffers: &CpuBufferPool<vs::ty::Camera>,
+  pipeline: &Arc<GraphicsPipeline>,
+  view: Matrix4<f32>,
+  projection: Matrix4<f32>,
+  position: [f32; 3],
+  lights: Arc<dyn BufferAccess>,
+  shadow_maps: &ShadowMaps,
+  graph: &RenderGraph,
+) -> Result<Arc<PersistentDescriptorSet>, Box<dyn Error>> {
+  let [x, y, z] = position;
+  let buffer = buffers.from_data(vs::ty::Camera {
+    view: view.into(),
+    proj: projection.into(),
+    position: [x, y, z, 1.0],
+  })?;
+
+  let layout = pipeline
+    .layout()
+    .set_layouts()
+    .get(0)
+    .ok_or("the pipeline has no camera set")?;
+  Ok(PersistentDescriptorSet::new(
+    layout.clone(),
+    [
+      WriteDescriptorSet::buffer(0, buffer),
+      WriteDescriptorSet::buffer(1, lights),
+      shadow_maps.descriptor_write(2, graph),
+    ],
+  )?)
+}
+
+// Shadow maps go to the first MAX_SHADOWS lights that cast shadows. Also
+// returns the matrices to draw each used map with.
+fn get_light_buffer(
+  buffers: &CpuBufferPool<fs::ty::Lights>,
+  lights: &[LightData],
+  ambient: [f32; 3],
+  shadow_maps: &ShadowMaps,
+) -> Result<(Arc<dyn BufferAccess>, Vec<Matrix4<f32>>), Box<dyn Error>> {
+  let options = shadow_maps.options();
+  let mut casters = Vec::new();
+
+  let mut uniform = fs::ty::Lights::zeroed();
+  for (slot, light) in uniform.lights.iter_mut().zip(lights) {
+    let map = match light.shadow_matrix {
+      Some(matrix) if casters.len() < MAX_SHADOWS => {
+        casters.push(matrix);
+        casters.len() as f32 - 1.0
+      }
+      _ => -1.0,
+    };
+
+    *slot = fs::ty::Light {
+      position: light.position,
+      direction: light.direction,
+      color: light.color,
+      cone: light.cone,
+      shadow_matrix: light.shadow_matrix.unwrap_or(Matrix4::identity()).into(),
+      shadow: [map, options.normal_bias, options.pcf_radius as f32, 0.0],
+    };
+  }
+
+  let [r, g, b] = ambient;
+  uniform.ambient = [r, g, b, 1.0];
+  uniform.count = lights.len().min(MAX_LIGHTS) as i32;
+
+  Ok((buffers.from_data(uniform)?, casters))
+}
+
+// With no cameras, actors are drawn straight into clip space over the whole
+// target.
+fn get_camera_views(
+  buffers: &CpuBufferPool<vs::ty::Camera>,
+  pipeline: &Arc<GraphicsPipeline>,
+  cameras: &[Camera],
+  viewport: &Viewport,
+  lights: Arc<dyn BufferAccess>,
+  shadow_maps: &ShadowMaps,
+  graph: &RenderGraph,
+) -> Result<Vec<(Viewport, Arc<PersistentDescriptorSet>)>, Box<dyn Error>> {
+  if cameras.is_empty() {
+    let set = get_camera_set(
+      buffers,
+      pipeline,
+      Matrix4::identity(),
+      Matrix4::identity(),
+      [0.0, 0.0, 0.0],
+      lights,
+      shadow_maps,
+      graph,
+    )?;
+    return Ok(vec![(viewport.clone(), set)]);
+  }
+
+  cameras
+    .iter()
+    .map(|camera| {
+      let set = get_camera_set(
+        buffers,
+        pipeline,
+        camera.view_matrix(),
+        camera.projection_matrix(),
+        camera.position,
+        lights.clone(),
+        shadow_maps,
+        graph,
+      )?;
+      Ok((get_camera_viewport(camera, viewport), set))
+    })
+    .collect()
+}
+
+fn get_camera_viewport(camera: &Camera, viewport: &Viewport) -> Viewport {
+  let [x, y, width, height] = camera.viewport;
+  let [target_width, target_height] = viewport.dimensions;
+
+  Viewport {
+    origin: [
+      viewport.origin[0] + x * target_width,
+      viewport.origin[1] + y * target_height,
+    ],
+    dimensions: [width * target_width, height * target_height],
+    depth_range: viewport.depth_range.clone(),
+  }
+}
+
+fn update_camera_aspects(cameras: &mut [Camera], viewport: &Viewport) {
+  for camera in cameras {
+    let [width, height] = get_camera_viewport(camera, viewport).dimensions;
+    camera.set_aspect(width / height);
+  }
+}
+
+fn get_command_buffers(
+  device: Arc<Device>,
+  queue: Arc<Queue>,
+  graph: &mut RenderGraph,
+  target: Arc<dyn ImageViewAbstract>,
+  camera_views: Vec<(Viewport, Arc<PersistentDescriptorSet>)>,
+  batches: &Vec<(Arc<GraphicsPipeline>, Vec<&Actor>)>,
+  shadow_maps: &ShadowMaps,
+  shadow_casters: &[Matrix4<f32>],
+  post: &PostStack,
+  capture: Option<(Arc<dyn ImageAccess>, Arc<CpuAccessibleBuffer<[u8]>>)>,
+) -> Result<Arc<PrimaryAutoCommandBuffer>, Box<dyn Error>> {
+  let mut builder = AutoCommandBufferBuilder::primary(
+    device,
+    queue.queue_family_index(),
+    CommandBufferUsage::MultipleSubmit,
+  )?;
+
+  set_active_shadows(graph, shadow_casters.len())?;
+  graph.execute(&mut builder, target, |pass, builder| {
+    if pass.name == SCENE_PASS {
+      return add_scene_pass(builder, &camera_views, batches);
+    }
+
+    match get_shadow_slot(&pass.name) {
+      Some(slot) => add_shadow_pass(
+        builder,
+        pass.viewport.clone(),
+        shadow_maps,
+        shadow_casters.get(slot),
+        batches,
+      ),
+      None => post.record(pass, builder),
+    }
+  })?;
+
+  if let Some((image, buffer)) = capture {
+    builder.copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buffer))?;
+  }
+
+  Ok(Arc::new(builder.build()?))
+}
+
+fn add_scene_pass(
+  builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+  camera_views: &[(Viewport, Arc<PersistentDescriptorSet>)],
+  batches: &Vec<(Arc<GraphicsPipeline>, Vec<&Actor>)>,
+) -> Result<(), Box<dyn Error>> {
+  for (viewport, camera_set) in camera_views {
+    builder.set_viewport(0, [viewport.clone()]);
+
+    for (pipeline, actors) in batches {
+      builder
+        .bind_pipeline_graphics(pipeline.clone())
+        .bind_descriptor_sets(
+          PipelineBindPoint::Graphics,
+          pipeline.layout().clone(),
+          0,
+          camera_set.clone(),
+        );
+
+      add_actor_buffers(builder, pipeline, actors)?;
+    }
+  }
+
+  Ok(())
+}
 
 fn add_actor_buffers(
   builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+  pipeline: &Arc<GraphicsPipeline>,
   actors: &Vec<&Actor>,
-) {
+) -> Result<(), Box<dyn Error>> {
   for actor in actors {
-    if let Some(buffer) = &actor.buffer {
-      builder
-        .bind_vertex_buffers(0, buffer.clone())
-        .draw(buffer.len() as u32, actor.tri_count, 0, 0)
-        .unwrap();
+    if !actor.visible || actor.draw_count == 0 {
+      continue;
+    }
+
+    let (Some(buffer), Some(instances)) = (&actor.buffer, &actor.instances) else {
+      continue;
+    };
+
+    builder
+      .push_constants(
+        pipeline.layout().clone(),
+        0,
+        vs::ty::Model {
+          model: actor.model_matrix().into(),
+        },
+      )
+      .bind_descriptor_sets(
+        PipelineBindPoint::Graphics,
+        pipeline.layout().clone(),
+        1,
+        actor.texture_set.clone(),
+      )
+      .bind_vertex_buffers(0, (buffer.clone(), instances.clone()));
+
+    draw_actor(builder, actor, instances.len() as u32)?;
+  }
+
+  Ok(())
+}
+
+// Every batched actor is drawn into the shadow map of a casting light, with the
+// light's matrix in place of the camera. Maps without a light are usually
+// skipped, and only cleared when the graph has just allocated them.
+fn add_shadow_pass(
+  builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+  viewport: Viewport,
+  shadow_maps: &ShadowMaps,
+  light: Option<&Matrix4<f32>>,
+  batches: &Vec<(Arc<GraphicsPipeline>, Vec<&Actor>)>,
+) -> Result<(), Box<dyn Error>> {
+  let Some(light) = light else {
+    return Ok(());
+  };
+
+  let options = shadow_maps.options();
+  let pipeline = shadow_maps.pipeline();
+
+  builder
+    .set_viewport(0, [viewport])
+    .set_depth_bias(options.depth_bias, 0.0, options.slope_bias)
+    .bind_pipeline_graphics(pipeline.clone());
+
+  for actor in batches.iter().flat_map(|(_, actors)| actors) {
+    if actor.draw_count == 0 {
+      continue;
+    }
+
+    let (Some(buffer), Some(instances)) = (&actor.buffer, &actor.instances) else {
+      continue;
+    };
+
+    builder
+      .push_constants(
+        pipeline.layout().clone(),
+        0,
+        shadow_vs::ty::Shadow {
+          model: actor.model_matrix().into(),
+          light: (*light).into(),
+        },
+      )
+      .bind_vertex_buffers(0, (buffer.clone(), instances.clone()));
+
+    draw_actor(builder, actor, instances.len() as u32)?;
+  }
+
+  Ok(())
+}
+
+fn draw_actor(
+  builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+  actor: &Actor,
+  instance_count: u32,
+) -> Result<(), Box<dyn Error>> {
+  match &actor.index_buffer {
+    Some(IndexBuffer::U16(indices)) => {
+      builder.bind_index_buffer(indices.clone()).draw_indexed(
+        actor.draw_count,
+        instance_count,
+        0,
+        0,
+        0,
+      )?;
+    }
+    Some(IndexBuffer::U32(indices)) => {
+      builder.bind_index_buffer(indices.clone()).draw_indexed(
+        actor.draw_count,
+        instance_count,
+        0,
+        0,
+        0,
+      )?;
+    }
+    None => {
+      builder.draw(actor.draw_count, instance_count, 0, 0)?;
     }
   }
+
+  Ok(())
+}
+
+fn get_actor(
+  actors: &mut HashMap<String, Actor>,
+  actor: String,
+) -> Result<&mut Actor, RendererError> {
+  actors
+    .get_mut(&actor)
+    .ok_or(RendererError::UnknownActor(actor))
+}
+
+fn mesh_fits(
+  buffer: &DeviceLocalBuffer<[Vertex]>,
+  index_buffer: &Option<IndexBuffer>,
+  mesh: &Mesh,
+) -> bool {
+  if buffer.len() < mesh.vertices.len() as u64 {
+    return false;
+  }
+
+  match (index_buffer, &mesh.indices) {
+    (None, None) => true,
+    (Some(IndexBuffer::U16(buffer)), Some(Indices::U16(indices))) => {
+      buffer.len() >= indices.len() as u64
+    }
+    (Some(IndexBuffer::U32(buffer)), Some(Indices::U32(indices))) => {
+      buffer.len() >= indices.len() as u64
+    }
+    _ => false,
+  }
+}
+
+fn get_model_matrix(translation: [f32; 3], rotation: [f32; 4], scale: [f32; 3]) -> Matrix4<f32> {
+  let [x, y, z, w] = rotation;
+  let [sx, sy, sz] = scale;
+
+  Matrix4::from_translation(Vector3::from(translation))
+    * Matrix4::from(Quaternion::new(w, x, y, z))
+    * Matrix4::from_nonuniform_scale(sx, sy, sz)
+}
+
+fn get_instance_buffer(
+  device: Arc<Device>,
+  instances: Vec<InstanceData>,
+) -> Result<Arc<CpuAccessibleBuffer<[InstanceData]>>, Box<dyn Error>> {
+  Ok(CpuAccessibleBuffer::from_iter(
+    device,
+    BufferUsage {
+      vertex_buffer: true,
+      ..Default::default()
+    },
+    false,
+    instances.into_iter(),
+  )?)
 }
 
 fn gen_id(length: Option<usize>) -> String {
@@ -541,3 +1982,134 @@ fn gen_id(length: Option<usize>) -> String {
     })
     .collect()
 }
+
+#[cfg(test)]
+mod vulkan_tests {
+  use super::*;
+  use crate::renderer::golden::{compare, headless_backend};
+
+  #[test]
+  fn test_unknown_actor() {
+    let Some(mut renderer) = headless_backend() else {
+      return;
+    };
+
+    let missing = String::from("missing");
+    assert!(matches!(
+      renderer.set_actor_translation(missing.clone(), [1.0, 0.0, 0.0]),
+      Err(RendererError::UnknownActor(_))
+    ));
+    assert!(matches!(
+      renderer.set_actor_transform(missing, [0.0; 3], [0.0, 0.0, 0.0, 1.0], [1.0; 3]),
+      Err(RendererError::UnknownActor(_))
+    ));
+  }
+
+  #[test]
+  fn test_clear_actor_instances() {
+    let Some(mut renderer) = headless_backend() else {
+      return;
+    };
+    crate::load_demo_scene(&mut renderer).unwrap();
+
+    assert!(!renderer.render());
+    let drawn = renderer.capture_frame().unwrap();
+    let clear = *drawn.get_pixel(0, 0);
+    let center = *drawn.get_pixel(drawn.width() / 2, drawn.height() / 2);
+    assert_ne!(center, clear);
+
+    // An empty Vec leaves the actor with nothing to draw.
+    let actor = String::from("test_actor");
+    renderer
+      .set_actor_instances(actor.clone(), vec![InstanceData::default(); 2])
+      .unwrap();
+    renderer
+      .set_actor_instances(actor.clone(), Vec::new())
+      .unwrap();
+    assert!(!renderer.render());
+    let frame = renderer.capture_frame().unwrap();
+    assert!(frame.pixels().all(|pixel| *pixel == clear));
+
+    // Clearing brings back the single untransformed instance.
+    renderer.clear_actor_instances(actor).unwrap();
+    assert!(!renderer.render());
+    let frame = renderer.capture_frame().unwrap();
+    assert_eq!(compare(&drawn, &frame, 0).mismatched, 0);
+  }
+
+  #[test]
+  fn test_render_after_graph_changes() {
+    let Some(mut renderer) = headless_backend() else {
+      return;
+    };
+    crate::load_demo_scene(&mut renderer).unwrap();
+    renderer.render_offscreen().unwrap();
+
+    renderer
+      .add_render_attachment(
+        String::from("overlay"),
+        AttachmentDesc::new(Format::R8G8B8A8_UNORM),
+      )
+      .unwrap();
+    renderer.render_offscreen().unwrap();
+
+    let ui = PassDesc::new("ui").color(TARGET);
+    renderer
+      .add_render_pass(ui, Box::new(|_, _| Ok(())))
+      .unwrap();
+    renderer.render_offscreen().unwrap();
+
+    renderer
+      .set_post_options(PostOptions {
+        fxaa: true,
+        ..Default::default()
+      })
+      .unwrap();
+    renderer.render_offscreen().unwrap();
+
+    renderer.remove_render_pass("ui").unwrap();
+    renderer.render_offscreen().unwrap();
+  }
+
+  #[test]
+  fn test_post_pass_position() {
+    let Some(mut renderer) = headless_backend() else {
+      return;
+    };
+
+    let ui = PassDesc::new("ui").color(TARGET);
+    renderer
+      .add_render_pass(ui, Box::new(|_, _| Ok(())))
+      .unwrap();
+    renderer
+      .set_post_options(PostOptions {
+        fxaa: true,
+        ..Default::default()
+      })
+      .unwrap();
+    renderer.render_offscreen().unwrap();
+
+    let order = renderer.graph.pass_order().unwrap();
+    assert_eq!(order[order.len() - 3..], ["tonemap", "fxaa", "ui"]);
+
+    // Reading the target into the tonemapped image only works without FXAA,
+    // so turning it back on fails and leaves the graph as it was.
+    renderer.set_post_options(PostOptions::default()).unwrap();
+    renderer.render_offscreen().unwrap();
+    let feedback = PassDesc::new("feedback").read(TARGET).color("ldr");
+    renderer
+      .add_render_pass(feedback, Box::new(|_, _| Ok(())))
+      .unwrap();
+    let before = renderer.graph.pass_order().unwrap().join(",");
+
+    assert!(renderer
+      .set_post_options(PostOptions {
+        fxaa: true,
+        ..Default::default()
+      })
+      .is_err());
+    assert_eq!(renderer.post_options(), PostOptions::default());
+    assert_eq!(renderer.graph.pass_order().unwrap().join(","), before);
+    renderer.render_offscreen().unwrap();
+  }
+}
@@ -1,3 +1,9 @@
 pub trait Event {
   fn as_any(&self) -> &dyn std::any::Any;
-}
\ No newline at end of file
+}
+
+pub trait RecordableEvent: Event + Sized {
+  fn event_name() -> &'static str;
+  fn serialize_event(&self) -> Option<String>;
+  fn deserialize_event(payload: &str) -> Option<Self>;
+}
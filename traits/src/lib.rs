@@ -2,6 +2,8 @@ use std::any::Any;
 
 pub mod event;
 
+pub use serde_json;
+
 pub trait StateQueue {
   fn stage<F>(&self, f: F)
   where